@@ -1,44 +1,288 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
 use eframe::egui;
+use egui_plot::{GridMark, Legend, Line, Plot, PlotUi, VLine};
+use qrcode::QrCode;
+
+use crate::dbus::{
+    AgentRequest, AgentRequestKind, AgentResponse, IwdDbus, IwdError, IwdEvent, SharedCodeRequest,
+};
+use crate::models::{
+    AccessPointInfo, ActiveTab, AdHocInfo, AdapterInfo, DeviceInfo, DeviceMode, DppInfo,
+    HiddenAccessPoint, KnownNetwork, NetworkSort, PkexInfo, PkexRole, Signal, StationDiagnostics,
+    StationInfo, StationState, VisibleNetwork, WpsMode, bitrate_mbps, signal_quality,
+};
+use crate::share;
+use crate::worker::{Command, OperationId, Outcome, Reply, Snapshot, Worker};
+
+const DEFAULT_AUTO_SCAN_INTERVAL_SECS: u64 = 30;
+/// How long an access point stays in WPS mode once its button is pressed.
+const WPS_WALK_TIME: Duration = Duration::from_secs(120);
+const DIAGNOSTICS_INTERVAL: Duration = Duration::from_secs(2);
+/// Ten minutes of samples at `DIAGNOSTICS_INTERVAL`.
+const LINK_HISTORY_LEN: usize = 300;
+
+#[derive(Debug)]
+struct PendingOperation {
+    id: OperationId,
+    command: Command,
+    canceled: bool,
+    /// Went through the worker queue rather than `submit_now`.
+    queued: bool,
+}
+
+#[derive(Debug)]
+struct AgentPrompt {
+    request: AgentRequest,
+    network_name: String,
+    user: String,
+    secret: String,
+}
+
+#[derive(Debug)]
+struct SharedCodePrompt {
+    request: SharedCodeRequest,
+    code: String,
+}
+
+#[derive(Debug, Default)]
+struct PkexDialog {
+    role: PkexRole,
+    code: String,
+    identifier: String,
+    /// Run a configurator that asks for each enrollee's code as it appears.
+    use_agent: bool,
+}
+
+#[derive(Debug)]
+struct ShareDialog {
+    ssid: String,
+    security: String,
+    hidden: bool,
+    passphrase: String,
+    show_passphrase: bool,
+    /// Export path without extension.
+    export_path: String,
+}
+
+/// Signal level agent registration of one station.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SignalLevelRegistration {
+    /// Registered or being registered, with the last level iwd reported while
+    /// connected.
+    Registered(Option<u8>),
+    /// iwd refused the agent for good, e.g. because another program already
+    /// watches the station; not retried while the station exists.
+    Failed,
+}
+
+#[derive(Debug)]
+struct LinkSample {
+    at: Instant,
+    rssi: Option<i16>,
+    rx_bitrate: Option<u32>,
+    tx_bitrate: Option<u32>,
+    frequency: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum LinkEvent {
+    Roamed,
+    Disconnected,
+}
+
+/// Diagnostics samples of one station in a ring buffer, plus the roams and
+/// disconnects seen while they were recorded.
+#[derive(Debug)]
+struct LinkHistory {
+    device_path: String,
+    started: Instant,
+    samples: VecDeque<LinkSample>,
+    events: Vec<(Instant, LinkEvent)>,
+}
+
+impl LinkHistory {
+    fn new(device_path: &str) -> Self {
+        Self {
+            device_path: device_path.to_string(),
+            started: Instant::now(),
+            samples: VecDeque::with_capacity(LINK_HISTORY_LEN),
+            events: Vec::new(),
+        }
+    }
+
+    fn push_sample(&mut self, sample: LinkSample) {
+        if self.samples.len() == LINK_HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        if let Some(oldest) = self.samples.front().map(|s| s.at) {
+            self.events.retain(|(at, _)| *at >= oldest);
+        }
+    }
+
+    /// Seconds since recording started, used as the X coordinate.
+    fn elapsed(&self, at: Instant) -> f64 {
+        at.duration_since(self.started).as_secs_f64()
+    }
+
+    fn series(&self, value: impl Fn(&LinkSample) -> Option<f64>) -> Vec<[f64; 2]> {
+        self.samples
+            .iter()
+            .filter_map(|sample| value(sample).map(|y| [self.elapsed(sample.at), y]))
+            .collect()
+    }
+}
 
-use crate::dbus::IwdDbus;
-use crate::models::{ActiveTab, DeviceInfo, KnownNetwork, VisibleNetwork};
+#[derive(Debug, Default)]
+struct WpsDialog {
+    mode: WpsMode,
+    pin: String,
+    started: Option<Instant>,
+}
 
 #[derive(Debug)]
 pub(crate) struct IwdGuiApp {
-    initialized: bool,
     active_tab: ActiveTab,
+    adapters: Vec<AdapterInfo>,
+    selected_adapter_path: Option<String>,
     devices: Vec<DeviceInfo>,
     selected_device_path: Option<String>,
+    stations: Vec<StationInfo>,
+    access_points: Vec<AccessPointInfo>,
+    ap_profiles: Vec<String>,
+    hotspot_ssid: String,
+    hotspot_passphrase: String,
+    hotspot_profile: String,
+    ad_hocs: Vec<AdHocInfo>,
+    ad_hoc_ssid: String,
+    ad_hoc_passphrase: String,
+    ad_hoc_open: bool,
+    dpps: Vec<DppInfo>,
+    dpp_peer_uri: String,
+    dpp_uri_file: String,
+    pkexes: Vec<PkexInfo>,
+    pkex: Option<PkexDialog>,
+    shared_code_prompt: Option<SharedCodePrompt>,
     visible_networks: Vec<VisibleNetwork>,
+    network_sort: NetworkSort,
+    auto_scan: bool,
+    auto_scan_interval_secs: u64,
+    last_auto_scan: Option<Instant>,
+    diagnostics: Option<StationDiagnostics>,
+    diagnostics_error: Option<String>,
+    last_diagnostics_poll: Option<Instant>,
+    /// Stations without link diagnostics, which are no longer polled.
+    diagnostics_unsupported: HashSet<String>,
+    link_history: Option<LinkHistory>,
+    /// Keep polling diagnostics for the history while the tab is not shown.
+    record_link_history: bool,
+    signal_level_agent: bool,
+    /// Stations the signal level agent was registered with, or failed to.
+    signal_levels: HashMap<String, SignalLevelRegistration>,
+    hidden_access_points: Vec<HiddenAccessPoint>,
     known_networks: Vec<KnownNetwork>,
+    hidden_ssid: Option<String>,
+    wps: Option<WpsDialog>,
+    share: Option<ShareDialog>,
     connect_ssid: String,
+    connect_username: String,
     connect_passphrase: String,
     selected_known_path: Option<String>,
     selected_known_details: String,
     selected_known_autoconnect: Option<bool>,
     status_line: String,
+    iwd_running: bool,
+    backend: Option<IwdDbus>,
+    events: Option<Receiver<IwdEvent>>,
+    worker: Option<Worker>,
+    pending: Vec<PendingOperation>,
+    agent_prompt: Option<AgentPrompt>,
+    /// Last retryable failure, with whether it went through the worker queue.
+    retry_command: Option<(Command, bool)>,
 }
 
-impl Default for IwdGuiApp {
-    fn default() -> Self {
-        Self {
-            initialized: false,
+impl IwdGuiApp {
+    pub(crate) fn new(ctx: &egui::Context) -> Self {
+        let mut app = Self {
             active_tab: ActiveTab::Networks,
+            adapters: Vec::new(),
+            selected_adapter_path: None,
             devices: Vec::new(),
             selected_device_path: None,
+            stations: Vec::new(),
+            access_points: Vec::new(),
+            ap_profiles: Vec::new(),
+            hotspot_ssid: String::new(),
+            hotspot_passphrase: String::new(),
+            hotspot_profile: String::new(),
+            ad_hocs: Vec::new(),
+            ad_hoc_ssid: String::new(),
+            ad_hoc_passphrase: String::new(),
+            ad_hoc_open: false,
+            dpps: Vec::new(),
+            dpp_peer_uri: String::new(),
+            dpp_uri_file: String::new(),
+            pkexes: Vec::new(),
+            pkex: None,
+            shared_code_prompt: None,
             visible_networks: Vec::new(),
+            network_sort: NetworkSort::Rank,
+            auto_scan: false,
+            auto_scan_interval_secs: DEFAULT_AUTO_SCAN_INTERVAL_SECS,
+            last_auto_scan: None,
+            diagnostics: None,
+            diagnostics_error: None,
+            last_diagnostics_poll: None,
+            diagnostics_unsupported: HashSet::new(),
+            link_history: None,
+            record_link_history: false,
+            signal_level_agent: false,
+            signal_levels: HashMap::new(),
+            hidden_access_points: Vec::new(),
             known_networks: Vec::new(),
+            hidden_ssid: None,
+            wps: None,
+            share: None,
             connect_ssid: String::new(),
+            connect_username: String::new(),
             connect_passphrase: String::new(),
             selected_known_path: None,
             selected_known_details: String::new(),
             selected_known_autoconnect: None,
             status_line: "Ready".to_string(),
+            iwd_running: true,
+            backend: None,
+            events: None,
+            worker: None,
+            pending: Vec::new(),
+            agent_prompt: None,
+            retry_command: None,
+        };
+
+        match IwdDbus::new() {
+            Ok(backend) => {
+                let repaint_ctx = ctx.clone();
+                app.worker = Some(Worker::spawn(backend.clone(), move || {
+                    repaint_ctx.request_repaint()
+                }));
+                let (tx, rx) = mpsc::channel();
+                app.events = Some(rx);
+                app.subscribe_to_changes(&backend, tx.clone(), ctx);
+                app.start_agent(&backend, tx, ctx);
+                app.backend = Some(backend);
+                app.refresh_all();
+            }
+            Err(err) => app.set_status(format!("Failed to connect to iwd D-Bus: {err}")),
         }
+        app
     }
-}
 
-impl IwdGuiApp {
     fn set_status(&mut self, status: impl Into<String>) {
         self.status_line = status.into();
     }
@@ -47,61 +291,347 @@ impl IwdGuiApp {
         self.devices
             .iter()
             .find(|d| Some(d.path.as_str()) == self.selected_device_path.as_deref())
-            .map(|d| d.name.clone())
+            .map(DeviceInfo::label)
             .unwrap_or_else(|| "(none)".to_string())
     }
 
-    fn refresh_all(&mut self) {
-        let backend = match IwdDbus::new() {
-            Ok(v) => v,
-            Err(err) => {
-                self.set_status(format!("Failed to connect to iwd D-Bus: {err}"));
-                return;
-            }
+    fn selected_device(&self) -> Option<&DeviceInfo> {
+        let path = self.selected_device_path.as_deref()?;
+        self.devices.iter().find(|d| d.path == path)
+    }
+
+    fn submit(&mut self, command: Command) {
+        self.dispatch(command, true);
+    }
+
+    /// Like `submit`, but bypasses the queue; see `Worker::submit_now`.
+    fn submit_now(&mut self, command: Command) {
+        self.dispatch(command, false);
+    }
+
+    fn dispatch(&mut self, command: Command, queued: bool) {
+        let Some(worker) = self.worker.as_mut() else {
+            self.set_status("Not connected to the system bus");
+            return;
+        };
+        let id = if queued {
+            worker.submit(command.clone())
+        } else {
+            worker.submit_now(command.clone())
+        };
+        self.pending.push(PendingOperation {
+            id,
+            command,
+            canceled: false,
+            queued,
+        });
+    }
+
+    fn is_pending(&self, matches: impl Fn(&Command) -> bool) -> bool {
+        self.pending
+            .iter()
+            .any(|op| !op.canceled && matches(&op.command))
+    }
+
+    /// Queued operations can always be dropped; running ones only when iwd
+    /// offers a way to abort them.
+    fn can_cancel(&self, op: &PendingOperation) -> bool {
+        let abortable = matches!(
+            op.command,
+            Command::Connect { .. }
+                | Command::ConnectHidden { .. }
+                | Command::WpsPushButton { .. }
+                | Command::WpsStartPin { .. }
+        );
+        abortable || (op.queued && self.worker.as_ref().is_some_and(|w| w.is_queued(op.id)))
+    }
+
+    fn cancel_operation(&mut self, id: OperationId) {
+        let Some(op) = self.pending.iter().find(|op| op.id == id) else {
+            return;
         };
+        let command = op.command.clone();
+        if !self.can_cancel(op) {
+            self.set_status(format!("{} is already running", command.label()));
+            return;
+        }
+        if let Some(op) = self.pending.iter_mut().find(|op| op.id == id) {
+            op.canceled = true;
+        }
+        if let Some(worker) = self.worker.as_ref() {
+            worker.cancel(id);
+        }
+        self.set_status(format!("{} canceled", command.label()));
 
-        match backend.list_devices() {
-            Ok(devices) => {
-                self.devices = devices;
-                if self.devices.is_empty() {
-                    self.selected_device_path = None;
-                    self.set_status("No wireless devices found");
-                } else if self
-                    .devices
-                    .iter()
-                    .all(|d| Some(d.path.as_str()) != self.selected_device_path.as_deref())
-                {
-                    self.selected_device_path = Some(self.devices[0].path.clone());
-                }
+        // Operations that are already in flight have to be stopped on the iwd
+        // side too.
+        match command {
+            Command::WpsPushButton { device_path } | Command::WpsStartPin { device_path, .. } => {
+                self.submit_now(Command::WpsCancel { device_path });
             }
-            Err(err) => {
-                self.set_status(format!("Failed to list devices: {err}"));
-                return;
+            Command::Connect { network_path, .. } => {
+                let device_path = self
+                    .visible_networks
+                    .iter()
+                    .find(|n| n.path == network_path)
+                    .and_then(|n| n.device_path.clone())
+                    .or_else(|| self.selected_device_path.clone());
+                self.abort_connect(device_path);
             }
+            Command::ConnectHidden { device_path, .. } => self.abort_connect(Some(device_path)),
+            _ => {}
+        }
+    }
+
+    /// iwd fails a pending `Network.Connect` with `Aborted` once the station
+    /// disconnects. Dropping the credentials request answers the agent call
+    /// in case iwd is still waiting on it.
+    fn abort_connect(&mut self, device_path: Option<String>) {
+        self.agent_prompt = None;
+        if let Some(device_path) = device_path {
+            self.submit_now(Command::Disconnect { device_path });
         }
+    }
 
-        let selected_device = self.selected_device_path.clone();
+    fn refresh_all(&mut self) {
+        if !self.is_pending(|c| matches!(c, Command::Refresh)) {
+            self.submit(Command::Refresh);
+        }
+    }
+
+    fn apply_pending_replies(&mut self) {
+        let replies = self
+            .worker
+            .as_ref()
+            .map(Worker::try_replies)
+            .unwrap_or_default();
+        for reply in replies {
+            self.handle_reply(reply);
+        }
+    }
+
+    fn handle_reply(&mut self, reply: Reply) {
+        let Some(index) = self.pending.iter().position(|op| op.id == reply.id) else {
+            return;
+        };
+        let op = self.pending.remove(index);
+        if op.canceled {
+            return;
+        }
 
-        match backend.list_visible_networks(selected_device.as_deref()) {
-            Ok(networks) => {
-                self.visible_networks = networks;
+        // Background polls report into the Diagnostics view, not the status line.
+        if let Command::GetDiagnostics { .. } = op.command {
+            self.apply_diagnostics(&op.command, reply.result);
+            return;
+        }
+        // Without the agent the indicator falls back to scan results. Transient
+        // failures are retried on the next station update, others never.
+        if let Command::RegisterSignalLevelAgent { device_path } = &op.command {
+            match reply.result {
+                Err(err) if err.is_retryable() => {
+                    self.signal_levels.remove(device_path);
+                }
+                Err(err) => {
+                    if let Some(registration) = self.signal_levels.get_mut(device_path) {
+                        *registration = SignalLevelRegistration::Failed;
+                    }
+                    if err == IwdError::AlreadyExists {
+                        self.set_status(describe_failure(&op.command, &err));
+                    }
+                }
+                Ok(_) => {}
             }
+            return;
+        }
+
+        self.retry_command = None;
+        let outcome = match reply.result {
+            Ok(outcome) => outcome,
             Err(err) => {
-                self.set_status(format!("Failed to load visible networks: {err}"));
+                self.set_status(describe_failure(&op.command, &err));
+                if err.is_retryable() {
+                    self.retry_command = Some((op.command, op.queued));
+                }
                 return;
             }
-        }
+        };
 
-        match backend.list_known_networks() {
-            Ok(known) => {
-                self.known_networks = known;
+        match (op.command, outcome) {
+            (_, Outcome::Canceled) => {}
+            (Command::Refresh, Outcome::Refreshed(snapshot)) => self.apply_snapshot(*snapshot),
+            (Command::Scan { .. }, _) => {
+                // Results are loaded once `Station.Scanning` drops back to false.
+                self.set_status("Scan started");
             }
-            Err(err) => {
-                self.set_status(format!("Failed to load saved networks: {err}"));
-                return;
+            (Command::Connect { ssid, .. }, _) => {
+                self.set_status(format!("Connect requested for `{ssid}`"));
+                self.refresh_all();
+            }
+            (Command::ConnectHidden { ssid, .. }, _) => {
+                self.set_status(format!("Connect requested for hidden `{ssid}`"));
+                self.refresh_all();
+            }
+            (Command::Disconnect { .. }, _) => {
+                self.set_status("Disconnected");
+                self.refresh_all();
+            }
+            (Command::Forget { known_path, name }, _) => {
+                if self.selected_known_path.as_deref() == Some(known_path.as_str()) {
+                    self.selected_known_path = None;
+                    self.selected_known_details.clear();
+                    self.selected_known_autoconnect = None;
+                }
+                self.set_status(format!("Forgot saved network `{name}`"));
+                self.refresh_all();
+            }
+            (Command::SetAutoConnect { .. }, _) => {
+                self.set_status("Updated AutoConnect");
+                self.refresh_all();
             }
+            (Command::SetAdapterPowered { powered, .. }, _) => {
+                self.set_status(if powered {
+                    "Adapter powered on"
+                } else {
+                    "Adapter powered off"
+                });
+                self.refresh_all();
+            }
+            (Command::SetDevicePowered { powered, .. }, _) => {
+                self.set_status(if powered {
+                    "Device powered on"
+                } else {
+                    "Device powered off"
+                });
+                self.refresh_all();
+            }
+            (Command::SetDeviceMode { mode, .. }, _) => {
+                self.set_status(format!("Switched to {} mode", mode.label().to_lowercase()));
+                self.refresh_all();
+            }
+            (Command::StartAccessPoint { ssid, .. }, _) => {
+                self.hotspot_passphrase.clear();
+                self.set_status(format!("Hotspot `{ssid}` started"));
+                self.refresh_all();
+            }
+            (Command::StartAccessPointProfile { profile, .. }, _) => {
+                self.set_status(format!("Hotspot started from profile `{profile}`"));
+                self.refresh_all();
+            }
+            (Command::StopAccessPoint { .. }, _) => {
+                self.set_status("Hotspot stopped");
+                self.refresh_all();
+            }
+            (Command::StartAdHoc { ssid, .. }, _) => {
+                self.ad_hoc_passphrase.clear();
+                self.set_status(format!("Ad-hoc network `{ssid}` started"));
+                self.refresh_all();
+            }
+            (Command::StopAdHoc { .. }, _) => {
+                self.set_status("Ad-hoc network stopped");
+                self.refresh_all();
+            }
+            (Command::WpsPushButton { .. } | Command::WpsStartPin { .. }, _) => {
+                self.wps = None;
+                self.set_status("Connected with WPS");
+                self.refresh_all();
+            }
+            (Command::WpsGeneratePin { .. }, Outcome::GeneratedPin(pin)) => {
+                if let Some(wps) = self.wps.as_mut() {
+                    wps.pin = pin;
+                }
+            }
+            (Command::WpsCancel { .. }, _) => {
+                if let Some(wps) = self.wps.as_mut() {
+                    wps.started = None;
+                }
+                self.set_status("WPS canceled");
+            }
+            (
+                Command::DppStartEnrollee { device_path }
+                | Command::DppStartConfigurator { device_path },
+                Outcome::DppUri(uri),
+            ) => {
+                // Show the code right away instead of waiting for the URI
+                // property to be signalled.
+                let mut dpp = self
+                    .dpps
+                    .iter()
+                    .find(|d| d.path == device_path)
+                    .cloned()
+                    .unwrap_or_default();
+                dpp.path = device_path;
+                dpp.started = true;
+                dpp.uri = Some(uri);
+                upsert_by_path(&mut self.dpps, dpp, |d| &d.path);
+                self.set_status("DPP started, scan the QR code with the other device");
+            }
+            (Command::DppConfigureEnrollee { .. }, _) => {
+                self.dpp_peer_uri.clear();
+                self.set_status("Provisioning the enrollee, waiting for it to respond");
+            }
+            (Command::DppStop { .. }, _) => {
+                self.set_status("DPP stopped");
+                self.refresh_all();
+            }
+            (Command::PkexStartEnrollee { .. }, _) => {
+                self.set_status("Waiting for a configurator with the same code");
+            }
+            (Command::PkexConfigureEnrollee { .. }, _) => {
+                self.set_status("Looking for an enrollee with the same code");
+            }
+            (Command::PkexStartConfigurator { .. }, _) => {
+                self.set_status("Shared code configurator started, waiting for enrollees");
+            }
+            (Command::PkexStop { .. }, _) => {
+                self.set_status("Shared code pairing stopped");
+                self.refresh_all();
+            }
+            (
+                Command::RegisterAgent
+                | Command::RegisterSignalLevelAgent { .. }
+                | Command::WpsGeneratePin { .. }
+                | Command::DppStartEnrollee { .. }
+                | Command::DppStartConfigurator { .. }
+                | Command::GetDiagnostics { .. }
+                | Command::Refresh,
+                _,
+            ) => {}
+        }
+    }
+
+    fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        self.adapters = snapshot.adapters;
+        if self
+            .adapters
+            .iter()
+            .all(|a| Some(a.path.as_str()) != self.selected_adapter_path.as_deref())
+        {
+            self.selected_adapter_path = self.adapters.first().map(|a| a.path.clone());
+        }
+        self.devices = snapshot.devices;
+        if self.devices.is_empty() {
+            self.selected_device_path = None;
+            self.set_status("No wireless devices found");
+        } else if self
+            .devices
+            .iter()
+            .all(|d| Some(d.path.as_str()) != self.selected_device_path.as_deref())
+        {
+            self.selected_device_path = Some(self.devices[0].path.clone());
         }
 
+        self.stations = snapshot.stations;
+        self.register_signal_level_agents();
+        self.access_points = snapshot.access_points;
+        self.ad_hocs = snapshot.ad_hocs;
+        self.dpps = snapshot.dpps;
+        self.pkexes = snapshot.pkexes;
+        self.ap_profiles = snapshot.ap_profiles;
+        self.visible_networks = snapshot.visible_networks;
+        self.sort_visible_networks();
+        self.hidden_access_points = snapshot.hidden_access_points;
+        self.known_networks = snapshot.known_networks;
+
         if let Some(path) = self.selected_known_path.clone() {
             if let Some(found) = self.known_networks.iter().find(|k| k.path == path) {
                 self.selected_known_details = format_known_network(found);
@@ -121,192 +651,2118 @@ impl IwdGuiApp {
         ));
     }
 
-    fn scan_networks(&mut self) {
-        let Some(device_path) = self.selected_device_path.clone() else {
-            self.set_status("Select a device first");
-            return;
-        };
-
-        let backend = match IwdDbus::new() {
-            Ok(v) => v,
-            Err(err) => {
-                self.set_status(format!("Failed to connect to iwd D-Bus: {err}"));
-                return;
-            }
-        };
-
-        match backend.scan(&device_path) {
-            Ok(_) => {
-                self.set_status("Scan requested");
-                self.refresh_all();
+    fn sort_visible_networks(&mut self) {
+        let by_ssid = |a: &VisibleNetwork, b: &VisibleNetwork| a.ssid.cmp(&b.ssid);
+        match self.network_sort {
+            NetworkSort::Rank => self.visible_networks.sort_by(|a, b| {
+                let rank = |n: &VisibleNetwork| n.rank.unwrap_or(usize::MAX);
+                rank(a).cmp(&rank(b)).then_with(|| by_ssid(a, b))
+            }),
+            NetworkSort::Signal => {
+                let levels = &self.signal_levels;
+                let signal =
+                    |n: &VisibleNetwork| network_signal(levels, n).map_or(i16::MIN, Signal::floor);
+                self.visible_networks
+                    .sort_by(|a, b| signal(b).cmp(&signal(a)).then_with(|| by_ssid(a, b)));
             }
-            Err(err) => self.set_status(format!("Scan failed: {err}")),
+            NetworkSort::Ssid => self.visible_networks.sort_by(by_ssid),
+            NetworkSort::Security => self
+                .visible_networks
+                .sort_by(|a, b| a.security.cmp(&b.security).then_with(|| by_ssid(a, b))),
         }
     }
 
-    fn connect_to_selected_network(&mut self) {
-        let ssid = self.connect_ssid.trim().to_string();
-        if ssid.is_empty() {
-            self.set_status("SSID cannot be empty");
-            return;
-        }
+    fn set_network_sort(&mut self, sort: NetworkSort) {
+        self.network_sort = sort;
+        self.sort_visible_networks();
+    }
 
-        let selected_device = self.selected_device_path.clone();
-        let candidate = self
-            .visible_networks
-            .iter()
-            .find(|n| {
-                n.ssid == ssid
-                    && (selected_device.is_none()
-                        || n.device_path.as_deref() == selected_device.as_deref())
-            })
-            .cloned();
+    fn subscribe_to_changes(
+        &mut self,
+        backend: &IwdDbus,
+        events: Sender<IwdEvent>,
+        ctx: &egui::Context,
+    ) {
+        let ctx = ctx.clone();
+        if let Err(err) = backend.subscribe(events, move || ctx.request_repaint()) {
+            self.set_status(format!("Failed to watch iwd for changes: {err}"));
+        }
+    }
 
-        let Some(network) = candidate else {
-            self.set_status("Selected SSID not found in visible list");
-            return;
-        };
+    fn start_agent(&mut self, backend: &IwdDbus, events: Sender<IwdEvent>, ctx: &egui::Context) {
+        let shared_code_ctx = ctx.clone();
+        if let Err(err) = backend
+            .serve_shared_code_agent(events.clone(), move || shared_code_ctx.request_repaint())
+        {
+            self.set_status(format!("Failed to start shared code agent: {err}"));
+        }
 
-        let backend = match IwdDbus::new() {
-            Ok(v) => v,
-            Err(err) => {
-                self.set_status(format!("Failed to connect to iwd D-Bus: {err}"));
-                return;
-            }
-        };
+        let signal_level_ctx = ctx.clone();
+        match backend
+            .serve_signal_level_agent(events.clone(), move || signal_level_ctx.request_repaint())
+        {
+            Ok(_) => self.signal_level_agent = true,
+            Err(err) => self.set_status(format!("Failed to start signal level agent: {err}")),
+        }
 
-        let passphrase = self.connect_passphrase.trim();
-        let passphrase = if passphrase.is_empty() {
-            None
-        } else {
-            Some(passphrase)
-        };
+        let ctx = ctx.clone();
+        match backend.serve_agent(events, move || ctx.request_repaint()) {
+            Ok(_) => self.submit(Command::RegisterAgent),
+            Err(err) => self.set_status(format!("Failed to start agent: {err}")),
+        }
+    }
 
-        match backend.connect_network(&network.path, passphrase) {
-            Ok(_) => {
-                self.set_status(format!("Connect requested for `{}`", network.ssid));
-                self.refresh_all();
-            }
-            Err(err) => self.set_status(format!("Connection failed: {err}")),
+    /// Registers the signal level agent with every station it is not yet
+    /// registered with, and forgets stations that are gone.
+    fn register_signal_level_agents(&mut self) {
+        if !self.signal_level_agent {
+            return;
+        }
+        self.signal_levels
+            .retain(|path, _| self.stations.iter().any(|s| s.path == *path));
+        let unregistered: Vec<String> = self
+            .stations
+            .iter()
+            .filter(|s| !self.signal_levels.contains_key(&s.path))
+            .map(|s| s.path.clone())
+            .collect();
+        for device_path in unregistered {
+            self.signal_levels.insert(
+                device_path.clone(),
+                SignalLevelRegistration::Registered(None),
+            );
+            self.submit(Command::RegisterSignalLevelAgent { device_path });
         }
     }
 
-    fn forget_known_network(&mut self, known_path: &str, name: &str) {
-        let backend = match IwdDbus::new() {
-            Ok(v) => v,
-            Err(err) => {
-                self.set_status(format!("Failed to connect to iwd D-Bus: {err}"));
-                return;
-            }
+    fn network_name(&self, network_path: &str) -> String {
+        let hidden_ssid = || {
+            self.pending.iter().find_map(|op| match &op.command {
+                Command::ConnectHidden { ssid, .. } => Some(ssid.clone()),
+                _ => None,
+            })
         };
+        self.visible_networks
+            .iter()
+            .find(|n| n.path == network_path)
+            .map(|n| n.ssid.clone())
+            .or_else(hidden_ssid)
+            .unwrap_or_else(|| network_path.to_string())
+    }
 
-        match backend.forget_known_network(known_path) {
-            Ok(_) => {
-                if self.selected_known_path.as_deref() == Some(known_path) {
-                    self.selected_known_path = None;
-                    self.selected_known_details.clear();
-                    self.selected_known_autoconnect = None;
+    fn handle_agent_request(&mut self, request: AgentRequest) {
+        let (typed_user, typed_secret) = self
+            .pending
+            .iter()
+            .filter(|op| !op.canceled)
+            .find_map(|op| match &op.command {
+                Command::Connect {
+                    network_path,
+                    username,
+                    passphrase,
+                    ..
+                } if *network_path == request.network_path => {
+                    Some((username.clone(), passphrase.clone()))
                 }
-                self.set_status(format!("Forgot saved network `{name}`"));
-                self.refresh_all();
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let typed_response = match (&request.kind, &typed_user, &typed_secret) {
+            (
+                AgentRequestKind::Passphrase | AgentRequestKind::UserPassword { .. },
+                _,
+                Some(secret),
+            ) => Some(AgentResponse {
+                secret: secret.clone(),
+                ..AgentResponse::default()
+            }),
+            (AgentRequestKind::UserNameAndPassword, Some(user), Some(secret)) => {
+                Some(AgentResponse {
+                    user: user.clone(),
+                    secret: secret.clone(),
+                })
             }
-            Err(err) => self.set_status(format!("Failed to forget `{name}`: {err}")),
+            _ => None,
+        };
+        if let Some(response) = typed_response {
+            request.respond(response);
+            return;
         }
-    }
 
-    fn select_known_network(&mut self, known: &KnownNetwork) {
-        self.selected_known_path = Some(known.path.clone());
-        self.selected_known_autoconnect = known.autoconnect;
-        self.selected_known_details = format_known_network(known);
-        self.set_status(format!("Loaded saved network details for `{}`", known.name));
+        let network_name = self.network_name(&request.network_path);
+        self.agent_prompt = Some(AgentPrompt {
+            request,
+            network_name,
+            user: typed_user.unwrap_or_default(),
+            secret: String::new(),
+        });
     }
 
-    fn set_known_autoconnect(&mut self, enabled: bool) {
-        let Some(path) = self.selected_known_path.clone() else {
-            self.set_status("Select a saved network first");
+    fn apply_pending_events(&mut self) {
+        let Some(events) = self.events.as_ref() else {
             return;
         };
-
-        let backend = match IwdDbus::new() {
-            Ok(v) => v,
-            Err(err) => {
-                self.set_status(format!("Failed to connect to iwd D-Bus: {err}"));
-                return;
-            }
-        };
-
-        match backend.set_known_autoconnect(&path, enabled) {
-            Ok(_) => {
-                self.set_status("Updated AutoConnect");
-                self.refresh_all();
-            }
-            Err(err) => self.set_status(format!("Failed to update AutoConnect: {err}")),
+        let pending: Vec<IwdEvent> = events.try_iter().collect();
+        for event in pending {
+            self.apply_event(event);
         }
     }
 
+    fn apply_event(&mut self, event: IwdEvent) {
+        match event {
+            IwdEvent::AdapterUpdated(adapter) => {
+                upsert_by_path(&mut self.adapters, adapter, |a| &a.path);
+                self.adapters.sort_by(|a, b| a.name.cmp(&b.name));
+                if self.selected_adapter_path.is_none() {
+                    self.selected_adapter_path = Some(self.adapters[0].path.clone());
+                }
+            }
+            IwdEvent::AdapterRemoved(path) => {
+                self.adapters.retain(|a| a.path != path);
+                if self.selected_adapter_path.as_deref() == Some(path.as_str()) {
+                    self.selected_adapter_path = self.adapters.first().map(|a| a.path.clone());
+                }
+            }
+            IwdEvent::DeviceUpdated(device) => {
+                upsert_by_path(&mut self.devices, device, |d| &d.path);
+                self.devices.sort_by(|a, b| a.name.cmp(&b.name));
+                if self.selected_device_path.is_none() {
+                    self.selected_device_path = Some(self.devices[0].path.clone());
+                }
+            }
+            IwdEvent::DeviceRemoved(path) => {
+                self.devices.retain(|d| d.path != path);
+                if self.selected_device_path.as_deref() == Some(path.as_str()) {
+                    self.selected_device_path = self.devices.first().map(|d| d.path.clone());
+                }
+            }
+            IwdEvent::NetworkUpdated(mut network) => {
+                // Property updates do not carry ranking; keep the last known one.
+                if let Some(existing) = self
+                    .visible_networks
+                    .iter()
+                    .find(|n| n.path == network.path)
+                {
+                    network.signal = network.signal.or(existing.signal);
+                    network.rank = network.rank.or(existing.rank);
+                }
+                upsert_by_path(&mut self.visible_networks, network, |n| &n.path);
+                self.sort_visible_networks();
+            }
+            IwdEvent::NetworkRemoved(path) => {
+                self.visible_networks.retain(|n| n.path != path);
+            }
+            IwdEvent::KnownNetworkUpdated(known) => {
+                if self.selected_known_path.as_deref() == Some(known.path.as_str()) {
+                    self.selected_known_details = format_known_network(&known);
+                    self.selected_known_autoconnect = known.autoconnect;
+                }
+                upsert_by_path(&mut self.known_networks, known, |k| &k.path);
+                self.known_networks.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+            IwdEvent::KnownNetworkRemoved(path) => {
+                self.known_networks.retain(|k| k.path != path);
+                if self.selected_known_path.as_deref() == Some(path.as_str()) {
+                    self.selected_known_path = None;
+                    self.selected_known_details.clear();
+                    self.selected_known_autoconnect = None;
+                }
+            }
+            IwdEvent::StationUpdated(station) => {
+                let previous = self.stations.iter().find(|s| s.path == station.path);
+                let scan_finished = !station.scanning && previous.is_some_and(|s| s.scanning);
+                let link_event = match (previous.map(|s| &s.state), &station.state) {
+                    (Some(from), StationState::Roaming) if *from != StationState::Roaming => {
+                        Some(LinkEvent::Roamed)
+                    }
+                    (
+                        Some(StationState::Connected | StationState::Roaming),
+                        StationState::Disconnecting | StationState::Disconnected,
+                    ) => Some(LinkEvent::Disconnected),
+                    _ => None,
+                };
+                if let Some(event) = link_event
+                    && let Some(history) = self.link_history.as_mut()
+                    && history.device_path == station.path
+                {
+                    history.events.push((Instant::now(), event));
+                }
+                if station.connected_network.is_none()
+                    && let Some(SignalLevelRegistration::Registered(level)) =
+                        self.signal_levels.get_mut(&station.path)
+                {
+                    *level = None;
+                }
+                upsert_by_path(&mut self.stations, station, |s| &s.path);
+                self.register_signal_level_agents();
+                if scan_finished {
+                    self.set_status("Scan finished");
+                    self.refresh_all();
+                }
+            }
+            IwdEvent::StationRemoved(path) => {
+                self.stations.retain(|s| s.path != path);
+                self.signal_levels.remove(&path);
+                self.diagnostics_unsupported.remove(&path);
+            }
+            IwdEvent::AccessPointUpdated(access_point) => {
+                upsert_by_path(&mut self.access_points, access_point, |a| &a.path);
+            }
+            IwdEvent::AccessPointRemoved(path) => {
+                self.access_points.retain(|a| a.path != path);
+            }
+            IwdEvent::AdHocUpdated(ad_hoc) => {
+                upsert_by_path(&mut self.ad_hocs, ad_hoc, |a| &a.path);
+            }
+            IwdEvent::AdHocRemoved(path) => {
+                self.ad_hocs.retain(|a| a.path != path);
+            }
+            IwdEvent::DppUpdated(dpp) => {
+                let finished =
+                    !dpp.started && self.dpps.iter().any(|d| d.path == dpp.path && d.started);
+                upsert_by_path(&mut self.dpps, dpp, |d| &d.path);
+                if finished {
+                    self.set_status("DPP session ended");
+                    self.refresh_all();
+                }
+            }
+            IwdEvent::DppRemoved(path) => {
+                self.dpps.retain(|d| d.path != path);
+            }
+            IwdEvent::PkexUpdated(pkex) => {
+                let finished =
+                    !pkex.started && self.pkexes.iter().any(|p| p.path == pkex.path && p.started);
+                upsert_by_path(&mut self.pkexes, pkex, |p| &p.path);
+                if finished {
+                    self.set_status("Shared code pairing ended");
+                    self.refresh_all();
+                }
+            }
+            IwdEvent::PkexRemoved(path) => {
+                self.pkexes.retain(|p| p.path != path);
+            }
+            IwdEvent::ServiceAppeared => {
+                self.iwd_running = true;
+                self.set_status("iwd is running again, reloading");
+                self.submit(Command::RegisterAgent);
+                self.refresh_all();
+            }
+            IwdEvent::ServiceVanished => {
+                self.iwd_running = false;
+                self.adapters.clear();
+                self.selected_adapter_path = None;
+                self.devices.clear();
+                self.selected_device_path = None;
+                self.stations.clear();
+                self.signal_levels.clear();
+                self.diagnostics_unsupported.clear();
+                self.access_points.clear();
+                self.ad_hocs.clear();
+                self.dpps.clear();
+                self.pkexes.clear();
+                self.shared_code_prompt = None;
+                self.visible_networks.clear();
+                self.hidden_access_points.clear();
+                self.known_networks.clear();
+                self.selected_known_path = None;
+                self.selected_known_details.clear();
+                self.selected_known_autoconnect = None;
+                self.agent_prompt = None;
+                self.set_status("iwd stopped");
+            }
+            IwdEvent::AgentRequest(request) => self.handle_agent_request(request),
+            IwdEvent::AgentCanceled(reason) => {
+                if self.agent_prompt.take().is_some() {
+                    self.set_status(format!("iwd canceled the credentials request: {reason}"));
+                }
+            }
+            IwdEvent::SharedCodeRequest(request) => {
+                self.shared_code_prompt = Some(SharedCodePrompt {
+                    request,
+                    code: String::new(),
+                });
+            }
+            IwdEvent::SharedCodeCanceled(reason) => {
+                if self.shared_code_prompt.take().is_some() {
+                    self.set_status(format!("iwd canceled the shared code request: {reason}"));
+                }
+            }
+            IwdEvent::SignalLevelChanged(path, level) => {
+                if let Some(SignalLevelRegistration::Registered(current)) =
+                    self.signal_levels.get_mut(&path)
+                {
+                    *current = Some(level);
+                    self.sort_visible_networks();
+                }
+            }
+            IwdEvent::SignalLevelAgentReleased(path) => {
+                self.signal_levels.remove(&path);
+            }
+        }
+    }
+
+    fn scan_networks(&mut self) {
+        let Some(device_path) = self.selected_device_path.clone() else {
+            self.set_status("Select a device first");
+            return;
+        };
+
+        self.submit(Command::Scan { device_path });
+    }
+
+    fn connect_to_selected_network(&mut self) {
+        let ssid = self.connect_ssid.trim().to_string();
+        if ssid.is_empty() {
+            self.set_status("SSID cannot be empty");
+            return;
+        }
+
+        let selected_device = self.selected_device_path.clone();
+        let candidate = self
+            .visible_networks
+            .iter()
+            .find(|n| {
+                n.ssid == ssid
+                    && (selected_device.is_none()
+                        || n.device_path.as_deref() == selected_device.as_deref())
+            })
+            .cloned();
+
+        let Some(network) = candidate else {
+            self.set_status("SSID not found in visible list; it may be a hidden network");
+            self.hidden_ssid = Some(ssid);
+            return;
+        };
+
+        let username = non_empty(&self.connect_username);
+        let passphrase = non_empty(&self.connect_passphrase);
+
+        // Connecting blocks until iwd is done, including while the agent waits
+        // for credentials, so it must not hold up the queue.
+        self.submit_now(Command::Connect {
+            network_path: network.path,
+            ssid: network.ssid,
+            username,
+            passphrase,
+        });
+    }
+
+    fn selected_network_is_enterprise(&self) -> bool {
+        let ssid = self.connect_ssid.trim();
+        self.visible_networks
+            .iter()
+            .any(|n| n.ssid == ssid && n.security == "8021x")
+    }
+
+    fn connect_to_hidden_network(&mut self, ssid: &str) {
+        let ssid = ssid.trim().to_string();
+        if ssid.is_empty() {
+            self.set_status("SSID cannot be empty");
+            return;
+        }
+        let Some(device_path) = self.selected_device_path.clone() else {
+            self.set_status("Select a device first");
+            return;
+        };
+
+        self.submit_now(Command::ConnectHidden { device_path, ssid });
+    }
+
+    fn selected_station(&self) -> Option<&StationInfo> {
+        let device_path = self.selected_device_path.as_deref()?;
+        self.stations.iter().find(|s| s.path == device_path)
+    }
+
+    fn is_scanning(&self) -> bool {
+        self.selected_station().is_some_and(|s| s.scanning)
+            || self.is_pending(|c| matches!(c, Command::Scan { .. }))
+    }
+
+    /// Why periodic scanning is currently on hold, if it is.
+    fn auto_scan_paused_reason(&self, ctx: &egui::Context) -> Option<&'static str> {
+        if self.active_tab != ActiveTab::Networks {
+            Some("Networks tab hidden")
+        } else if ctx.input(|i| i.viewport().minimized.unwrap_or(false)) {
+            Some("window minimized")
+        } else if self.selected_station().is_none() {
+            Some("not in station mode")
+        } else if self
+            .selected_station()
+            .is_some_and(|s| s.connected_network.is_some())
+        {
+            Some("connected")
+        } else {
+            None
+        }
+    }
+
+    fn apply_diagnostics(&mut self, command: &Command, result: Result<Outcome, IwdError>) {
+        match result {
+            Ok(Outcome::Diagnostics(diagnostics)) => {
+                if let Command::GetDiagnostics { device_path } = command {
+                    self.record_link_sample(device_path, &diagnostics);
+                }
+                self.diagnostics = Some(*diagnostics);
+                self.diagnostics_error = None;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                if let Command::GetDiagnostics { device_path } = command
+                    && diagnostics_unsupported(&err)
+                {
+                    self.diagnostics_unsupported.insert(device_path.clone());
+                }
+                self.diagnostics = None;
+                self.diagnostics_error = Some(describe_failure(command, &err));
+            }
+        }
+    }
+
+    fn record_link_sample(&mut self, device_path: &str, diagnostics: &StationDiagnostics) {
+        if self
+            .link_history
+            .as_ref()
+            .is_some_and(|history| history.device_path != device_path)
+        {
+            self.link_history = None;
+        }
+        self.link_history
+            .get_or_insert_with(|| LinkHistory::new(device_path))
+            .push_sample(LinkSample {
+                at: Instant::now(),
+                rssi: diagnostics.rssi,
+                rx_bitrate: diagnostics.rx_bitrate,
+                tx_bitrate: diagnostics.tx_bitrate,
+                frequency: diagnostics.frequency,
+            });
+    }
+
+    /// Polls `StationDiagnostic.GetDiagnostics` while the selected station is
+    /// connected and its driver reports diagnostics, as long as the Diagnostics
+    /// tab is open or the history is being recorded in the background.
+    fn run_diagnostics_poll(&mut self, ctx: &egui::Context) {
+        if self.active_tab != ActiveTab::Diagnostics && !self.record_link_history {
+            return;
+        }
+        let connected = self
+            .selected_station()
+            .filter(|station| station.state == StationState::Connected)
+            .map(|station| station.path.clone());
+        let Some(device_path) = connected else {
+            self.diagnostics = None;
+            self.diagnostics_error = None;
+            return;
+        };
+        if self.diagnostics_unsupported.contains(&device_path) {
+            if self.diagnostics_error.is_none() {
+                let command = Command::GetDiagnostics { device_path };
+                self.diagnostics_error = Some(describe_failure(&command, &IwdError::NotSupported));
+            }
+            return;
+        }
+
+        let due = self
+            .last_diagnostics_poll
+            .is_none_or(|last| last.elapsed() >= DIAGNOSTICS_INTERVAL);
+        if due && !self.is_pending(|c| matches!(c, Command::GetDiagnostics { .. })) {
+            self.last_diagnostics_poll = Some(Instant::now());
+            self.submit(Command::GetDiagnostics { device_path });
+        }
+        ctx.request_repaint_after(DIAGNOSTICS_INTERVAL);
+    }
+
+    fn run_auto_scan(&mut self, ctx: &egui::Context) {
+        if !self.auto_scan || self.auto_scan_paused_reason(ctx).is_some() {
+            return;
+        }
+
+        let interval = Duration::from_secs(self.auto_scan_interval_secs);
+        let due = self
+            .last_auto_scan
+            .is_none_or(|last| last.elapsed() >= interval);
+        if due && !self.is_scanning() && self.selected_device_path.is_some() {
+            self.last_auto_scan = Some(Instant::now());
+            self.scan_networks();
+        }
+
+        let next = self
+            .last_auto_scan
+            .map(|last| interval.saturating_sub(last.elapsed()))
+            .unwrap_or(interval);
+        ctx.request_repaint_after(next.max(Duration::from_secs(1)));
+    }
+
+    fn connected_network(&self) -> Option<&VisibleNetwork> {
+        let network_path = self.selected_station()?.connected_network.as_deref()?;
+        self.visible_networks
+            .iter()
+            .find(|n| n.path == network_path)
+    }
+
+    fn disconnect_station(&mut self) {
+        let Some(device_path) = self.selected_device_path.clone() else {
+            self.set_status("Select a device first");
+            return;
+        };
+
+        self.submit(Command::Disconnect { device_path });
+    }
+
+    fn draw_disconnect_button(&mut self, ui: &mut egui::Ui) {
+        if self.is_pending(|c| matches!(c, Command::Disconnect { .. })) {
+            ui.spinner();
+        } else if ui.button("Disconnect").clicked() {
+            self.disconnect_station();
+        }
+    }
+
+    /// Draws Powered toggles for the selected device and its adapter. iwd drops
+    /// the device objects of a powered-off adapter, so those adapters get a
+    /// separate "Power on" button.
+    fn draw_power_toggles(&mut self, ui: &mut egui::Ui) {
+        if self.is_pending(|c| {
+            matches!(
+                c,
+                Command::SetAdapterPowered { .. } | Command::SetDevicePowered { .. }
+            )
+        }) {
+            ui.spinner();
+            return;
+        }
+
+        if let Some(device) = self.selected_device().cloned() {
+            let mut powered = device.powered;
+            if ui.checkbox(&mut powered, "Device on").changed() {
+                self.submit(Command::SetDevicePowered {
+                    device_path: device.path.clone(),
+                    powered,
+                });
+            }
+
+            let adapter = device
+                .adapter_path
+                .as_deref()
+                .and_then(|path| self.adapters.iter().find(|a| a.path == path))
+                .cloned();
+            if let Some(adapter) = adapter {
+                let mut powered = adapter.powered;
+                if ui
+                    .checkbox(&mut powered, format!("Adapter {} on", adapter.name))
+                    .changed()
+                {
+                    self.submit(Command::SetAdapterPowered {
+                        adapter_path: adapter.path,
+                        powered,
+                    });
+                }
+            }
+        }
+
+        let unpowered: Vec<AdapterInfo> = self
+            .adapters
+            .iter()
+            .filter(|a| !a.powered)
+            .filter(|a| {
+                self.devices
+                    .iter()
+                    .all(|d| d.adapter_path.as_deref() != Some(a.path.as_str()))
+            })
+            .cloned()
+            .collect();
+        for adapter in unpowered {
+            ui.separator();
+            ui.label(format!("Adapter {} is off", adapter.name));
+            if ui.button("Power on").clicked() {
+                self.submit(Command::SetAdapterPowered {
+                    adapter_path: adapter.path,
+                    powered: true,
+                });
+            }
+        }
+    }
+
+    fn device_adapter(&self, device: &DeviceInfo) -> Option<&AdapterInfo> {
+        let path = device.adapter_path.as_deref()?;
+        self.adapters.iter().find(|a| a.path == path)
+    }
+
+    fn set_device_mode(&mut self, device: &DeviceInfo, mode: DeviceMode) {
+        if let Some(adapter) = self.device_adapter(device)
+            && !adapter.supports(&mode)
+        {
+            let status = format!(
+                "Adapter {} does not support {} mode",
+                adapter.name,
+                mode.label().to_lowercase()
+            );
+            self.set_status(status);
+            return;
+        }
+
+        self.submit(Command::SetDeviceMode {
+            device_path: device.path.clone(),
+            mode,
+        });
+    }
+
+    /// Draws a `Device.Mode` selector offering only the modes listed in the
+    /// adapter's `SupportedModes`.
+    fn draw_mode_selector(&mut self, ui: &mut egui::Ui, device: &DeviceInfo) {
+        let switching = self.is_pending(
+            |c| matches!(c, Command::SetDeviceMode { device_path, .. } if *device_path == device.path),
+        );
+        if switching {
+            ui.spinner();
+            return;
+        }
+
+        let adapter = self.device_adapter(device).cloned();
+        let mut selected = None;
+        egui::ComboBox::from_id_salt(("device_mode", &device.path))
+            .selected_text(device.mode.label())
+            .show_ui(ui, |ui| {
+                for mode in DeviceMode::SELECTABLE {
+                    let supported = adapter.as_ref().is_none_or(|a| a.supports(&mode));
+                    let response = ui
+                        .add_enabled(
+                            supported,
+                            egui::Button::selectable(device.mode == mode, mode.label()),
+                        )
+                        .on_disabled_hover_text("Not supported by this adapter");
+                    if response.clicked() && device.mode != mode {
+                        selected = Some(mode);
+                    }
+                }
+            });
+
+        if let Some(mode) = selected {
+            self.set_device_mode(device, mode);
+        }
+    }
+
+    /// Shown in place of the station UI while the selected device is in
+    /// another mode.
+    fn draw_mode_panel(&mut self, ui: &mut egui::Ui, device: &DeviceInfo) {
+        ui.heading(format!(
+            "{} is in {} mode",
+            device.name,
+            device.mode.label().to_lowercase()
+        ));
+        ui.label("Scanning and connecting to networks needs station mode.");
+        ui.add_space(6.0);
+
+        ui.horizontal(|ui| {
+            self.draw_switch_mode_button(ui, device, DeviceMode::Station);
+            if device.mode == DeviceMode::AccessPoint && ui.button("Manage hotspot").clicked() {
+                self.active_tab = ActiveTab::Hotspot;
+            }
+        });
+    }
+
+    fn draw_switch_mode_button(
+        &mut self,
+        ui: &mut egui::Ui,
+        device: &DeviceInfo,
+        mode: DeviceMode,
+    ) {
+        let supported = self
+            .device_adapter(device)
+            .is_none_or(|a| a.supports(&mode));
+        let label = format!("Switch to {} mode", mode.label().to_lowercase());
+        if self.is_pending(|c| matches!(c, Command::SetDeviceMode { .. })) {
+            ui.spinner();
+        } else if ui
+            .add_enabled(supported, egui::Button::new(label))
+            .on_disabled_hover_text("Not supported by this adapter")
+            .clicked()
+        {
+            self.set_device_mode(device, mode);
+        }
+    }
+
+    fn selected_access_point(&self) -> Option<&AccessPointInfo> {
+        let path = self.selected_device_path.as_deref()?;
+        self.access_points.iter().find(|a| a.path == path)
+    }
+
+    fn start_hotspot(&mut self, device_path: String) {
+        let Some(ssid) = non_empty(&self.hotspot_ssid) else {
+            self.set_status("Enter an SSID for the hotspot");
+            return;
+        };
+        let passphrase = self.hotspot_passphrase.clone();
+        if !(8..=63).contains(&passphrase.chars().count()) {
+            self.set_status("The hotspot passphrase must be 8 to 63 characters");
+            return;
+        }
+
+        self.submit(Command::StartAccessPoint {
+            device_path,
+            ssid,
+            passphrase,
+        });
+    }
+
+    fn start_hotspot_profile(&mut self, device_path: String) {
+        let Some(profile) = non_empty(&self.hotspot_profile) else {
+            self.set_status("Enter the name of a profile in /var/lib/iwd/ap");
+            return;
+        };
+
+        self.submit(Command::StartAccessPointProfile {
+            device_path,
+            profile,
+        });
+    }
+
+    fn forget_known_network(&mut self, known_path: &str, name: &str) {
+        self.submit(Command::Forget {
+            known_path: known_path.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    fn select_known_network(&mut self, known: &KnownNetwork) {
+        self.selected_known_path = Some(known.path.clone());
+        self.selected_known_autoconnect = known.autoconnect;
+        self.selected_known_details = format_known_network(known);
+        self.set_status(format!("Loaded saved network details for `{}`", known.name));
+    }
+
+    fn set_known_autoconnect(&mut self, enabled: bool) {
+        let Some(path) = self.selected_known_path.clone() else {
+            self.set_status("Select a saved network first");
+            return;
+        };
+
+        self.submit(Command::SetAutoConnect {
+            known_path: path,
+            enabled,
+        });
+    }
+
+    fn draw_agent_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = self.agent_prompt.as_mut() else {
+            return;
+        };
+
+        let (title, what) = match &prompt.request.kind {
+            AgentRequestKind::Passphrase => ("Passphrase required", "a passphrase".to_string()),
+            AgentRequestKind::PrivateKeyPassphrase => (
+                "Private key passphrase required",
+                "the private key passphrase".to_string(),
+            ),
+            AgentRequestKind::UserNameAndPassword => (
+                "Credentials required",
+                "a username and password".to_string(),
+            ),
+            AgentRequestKind::UserPassword { user } => {
+                ("Password required", format!("the password of `{user}`"))
+            }
+        };
+        let needs_user = prompt.request.kind == AgentRequestKind::UserNameAndPassword;
+
+        let mut submit = false;
+        let mut dismiss = false;
+        let modal = egui::Modal::new(egui::Id::new("agent_prompt")).show(ctx, |ui| {
+            ui.heading(title);
+            ui.label(format!("`{}` is asking for {what}.", prompt.network_name));
+            if needs_user {
+                ui.horizontal(|ui| {
+                    ui.label("Username");
+                    ui.text_edit_singleline(&mut prompt.user);
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Passphrase");
+                let field = ui.add(egui::TextEdit::singleline(&mut prompt.secret).password(true));
+                if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submit = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("OK").clicked() {
+                    submit = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    dismiss = true;
+                }
+            });
+        });
+
+        let missing_user = needs_user && prompt.user.trim().is_empty();
+        if submit && missing_user {
+            self.set_status("Username cannot be empty");
+        } else if submit {
+            if let Some(prompt) = self.agent_prompt.take() {
+                self.set_status(format!("Sent credentials for `{}`", prompt.network_name));
+                prompt.request.respond(AgentResponse {
+                    user: prompt.user,
+                    secret: prompt.secret,
+                });
+            }
+        } else if dismiss || modal.should_close() {
+            self.agent_prompt = None;
+            self.set_status("Credentials request dismissed");
+        }
+    }
+
+    fn draw_hidden_network_dialog(&mut self, ctx: &egui::Context) {
+        let Some(ssid) = self.hidden_ssid.as_mut() else {
+            return;
+        };
+
+        let mut submit = false;
+        let mut dismiss = false;
+        let modal = egui::Modal::new(egui::Id::new("hidden_network_dialog")).show(ctx, |ui| {
+            ui.heading("Connect to hidden network");
+            ui.horizontal(|ui| {
+                ui.label("SSID");
+                let field = ui.text_edit_singleline(ssid);
+                if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submit = true;
+                }
+            });
+            ui.label("You will be asked for a passphrase if the network needs one.");
+            ui.horizontal(|ui| {
+                if ui.button("Connect").clicked() {
+                    submit = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    dismiss = true;
+                }
+            });
+        });
+
+        if submit {
+            if let Some(ssid) = self.hidden_ssid.take() {
+                self.connect_to_hidden_network(&ssid);
+            }
+        } else if dismiss || modal.should_close() {
+            self.hidden_ssid = None;
+        }
+    }
+
+    fn wps_operation(&self) -> Option<&PendingOperation> {
+        self.pending.iter().find(|op| {
+            !op.canceled
+                && matches!(
+                    op.command,
+                    Command::WpsPushButton { .. } | Command::WpsStartPin { .. }
+                )
+        })
+    }
+
+    fn start_wps(&mut self) {
+        let Some(device_path) = self.selected_device_path.clone() else {
+            self.set_status("Select a device first");
+            return;
+        };
+        let Some(wps) = self.wps.as_mut() else {
+            return;
+        };
+
+        let command = match wps.mode {
+            WpsMode::PushButton => Command::WpsPushButton { device_path },
+            WpsMode::Pin => {
+                let pin: String = wps.pin.chars().filter(|c| !c.is_whitespace()).collect();
+                if !matches!(pin.len(), 4 | 8) || !pin.chars().all(|c| c.is_ascii_digit()) {
+                    self.set_status("A WPS PIN is 4 or 8 digits");
+                    return;
+                }
+                Command::WpsStartPin { device_path, pin }
+            }
+        };
+        // The call blocks for up to the walk time, so keep it off the queue.
+        wps.started = Some(Instant::now());
+        self.submit_now(command);
+    }
+
+    fn draw_wps_dialog(&mut self, ctx: &egui::Context) {
+        if self.wps.is_none() {
+            return;
+        }
+
+        let running = self.wps_operation().map(|op| op.id);
+        let generating = self.is_pending(|c| matches!(c, Command::WpsGeneratePin { .. }));
+        let Some(wps) = self.wps.as_mut() else {
+            return;
+        };
+
+        let mut start = false;
+        let mut generate = false;
+        let mut cancel = false;
+        let mut dismiss = false;
+        let modal = egui::Modal::new(egui::Id::new("wps_dialog")).show(ctx, |ui| {
+            ui.heading("Connect with WPS");
+
+            if running.is_some() {
+                let remaining = wps
+                    .started
+                    .map(|started| WPS_WALK_TIME.saturating_sub(started.elapsed()))
+                    .unwrap_or(WPS_WALK_TIME);
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    match wps.mode {
+                        WpsMode::PushButton => ui.label("Press the WPS button on the access point"),
+                        WpsMode::Pin => {
+                            ui.label(format!("Enter PIN {} on the access point", wps.pin.trim()))
+                        }
+                    };
+                });
+                ui.label(format!("{} s left", remaining.as_secs()));
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+                ctx.request_repaint_after(Duration::from_secs(1));
+                return;
+            }
+
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut wps.mode, WpsMode::PushButton, "Push button");
+                ui.radio_value(&mut wps.mode, WpsMode::Pin, "PIN");
+            });
+            match wps.mode {
+                WpsMode::PushButton => {
+                    ui.label("Start, then press the WPS button on the access point.");
+                }
+                WpsMode::Pin => {
+                    ui.horizontal(|ui| {
+                        ui.label("PIN");
+                        ui.text_edit_singleline(&mut wps.pin);
+                        if generating {
+                            ui.spinner();
+                        } else if ui.button("Generate").clicked() {
+                            generate = true;
+                        }
+                    });
+                    ui.label("Enter this PIN in the access point's WPS settings.");
+                }
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Start").clicked() {
+                    start = true;
+                }
+                if ui.button("Close").clicked() {
+                    dismiss = true;
+                }
+            });
+        });
+
+        if start {
+            self.start_wps();
+        } else if generate {
+            if let Some(device_path) = self.selected_device_path.clone() {
+                self.submit(Command::WpsGeneratePin { device_path });
+            }
+        } else if cancel || (running.is_some() && modal.should_close()) {
+            if let Some(id) = running {
+                self.cancel_operation(id);
+            }
+        } else if dismiss || modal.should_close() {
+            self.wps = None;
+        }
+    }
+
+    fn open_share(&mut self, ssid: &str, security: &str, hidden: bool) {
+        if security == "8021x" {
+            self.set_status("Enterprise networks cannot be shared as a QR code");
+            return;
+        }
+
+        let typed = (self.connect_ssid.trim() == ssid && !self.connect_passphrase.is_empty())
+            .then(|| self.connect_passphrase.clone());
+        let passphrase = match security {
+            "open" => None,
+            "psk" => typed.or_else(|| share::profile_passphrase(ssid)),
+            _ => typed,
+        }
+        .unwrap_or_default();
+
+        let file_name: String = ssid
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let dir = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        self.share = Some(ShareDialog {
+            ssid: ssid.to_string(),
+            security: security.to_string(),
+            hidden,
+            passphrase,
+            show_passphrase: false,
+            export_path: format!("{dir}/wifi-{file_name}"),
+        });
+    }
+
+    fn export_share(&mut self, extension: &str) {
+        let Some(share) = self.share.as_ref() else {
+            return;
+        };
+        let payload = share::wifi_payload(
+            &share.ssid,
+            &share.security,
+            &share.passphrase,
+            share.hidden,
+        );
+        let path = format!("{}.{extension}", share.export_path.trim());
+        let result = match extension {
+            "svg" => share::export_svg(&payload, Path::new(&path)),
+            _ => share::export_png(&payload, Path::new(&path)),
+        };
+        match result {
+            Ok(()) => self.set_status(format!("Saved QR code to {path}")),
+            Err(err) => self.set_status(format!("Failed to save {path}: {err}")),
+        }
+    }
+
+    fn draw_share_window(&mut self, ctx: &egui::Context) {
+        let Some(share) = self.share.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut export = None;
+        egui::Window::new("Share network")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(&share.ssid);
+                let needs_passphrase = share.security != "open";
+                if needs_passphrase {
+                    ui.horizontal(|ui| {
+                        ui.label("Passphrase");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut share.passphrase)
+                                .password(!share.show_passphrase),
+                        );
+                        ui.checkbox(&mut share.show_passphrase, "Show");
+                    });
+                }
+
+                if needs_passphrase && share.passphrase.is_empty() {
+                    ui.label("Enter the passphrase to generate the code.");
+                } else {
+                    let payload = share::wifi_payload(
+                        &share.ssid,
+                        &share.security,
+                        &share.passphrase,
+                        share.hidden,
+                    );
+                    ui.label("Scan with a phone camera to join:");
+                    draw_qr_code(ui, &payload, 260.0);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Save as");
+                        ui.text_edit_singleline(&mut share.export_path);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Save PNG").clicked() {
+                            export = Some("png");
+                        }
+                        if ui.button("Save SVG").clicked() {
+                            export = Some("svg");
+                        }
+                    });
+                }
+            });
+
+        if let Some(extension) = export {
+            self.export_share(extension);
+        }
+        if !open {
+            self.share = None;
+        }
+    }
+
+    fn draw_hidden_access_points(&mut self, ui: &mut egui::Ui) {
+        let selected_device = self.selected_device_path.clone();
+        let hidden: Vec<&HiddenAccessPoint> = self
+            .hidden_access_points
+            .iter()
+            .filter(|ap| Some(ap.device_path.as_str()) == selected_device.as_deref())
+            .collect();
+
+        egui::CollapsingHeader::new(format!("Hidden Access Points ({})", hidden.len()))
+            .id_salt("hidden_access_points")
+            .show(ui, |ui| {
+                if hidden.is_empty() {
+                    ui.label("No hidden access points seen in the last scan");
+                    return;
+                }
+                egui::Grid::new("hidden_access_points_grid")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Address");
+                        ui.strong("Security");
+                        ui.strong("Signal");
+                        ui.end_row();
+
+                        for ap in hidden {
+                            ui.label(&ap.address);
+                            ui.label(&ap.security);
+                            draw_signal_indicator(ui, Some(Signal::Exact(ap.signal)));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn draw_station_status(&mut self, ui: &mut egui::Ui) {
+        let Some(station) = self.selected_station().cloned() else {
+            ui.label("The selected device is not in station mode");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            match station.connected_network.as_deref() {
+                Some(path) => {
+                    let name = self.network_name(path);
+                    ui.heading(format!("Connected to {name}"));
+                }
+                None => {
+                    ui.heading("Not connected");
+                }
+            }
+            if station.connected_network.is_some() {
+                self.draw_disconnect_button(ui);
+                if let Some(network) = self.connected_network().cloned()
+                    && ui
+                        .add_enabled(network.security != "8021x", egui::Button::new("Share"))
+                        .on_disabled_hover_text("Enterprise networks cannot be shared")
+                        .clicked()
+                {
+                    let hidden = self
+                        .known_networks
+                        .iter()
+                        .any(|k| k.name == network.ssid && k.hidden == Some(true));
+                    self.open_share(&network.ssid, &network.security, hidden);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("State:");
+            let color = match station.state {
+                StationState::Connected => ui.visuals().strong_text_color(),
+                StationState::Disconnected => ui.visuals().weak_text_color(),
+                _ => ui.visuals().warn_fg_color,
+            };
+            ui.colored_label(color, station.state.label());
+            if matches!(
+                station.state,
+                StationState::Connecting | StationState::Roaming | StationState::Disconnecting
+            ) {
+                ui.spinner();
+            }
+            if let Some(network) = self.connected_network() {
+                ui.separator();
+                ui.label("Signal:");
+                draw_signal_indicator(ui, network_signal(&self.signal_levels, network));
+            }
+        });
+    }
+
     fn draw_networks_tab(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
-            if ui.button("Scan").clicked() {
-                self.scan_networks();
+            let scanning = self.is_scanning();
+            if ui
+                .add_enabled(!scanning, egui::Button::new("Scan"))
+                .on_disabled_hover_text("A scan is already in progress")
+                .clicked()
+            {
+                self.scan_networks();
+            }
+            if scanning {
+                ui.spinner();
+                ui.label("Scanning…");
+            }
+            if ui.button("Refresh").clicked() {
+                self.refresh_all();
+            }
+
+            ui.separator();
+            if ui
+                .checkbox(&mut self.auto_scan, "Auto-scan every")
+                .changed()
+            {
+                self.last_auto_scan = None;
+            }
+            ui.add(
+                egui::DragValue::new(&mut self.auto_scan_interval_secs)
+                    .range(5..=600)
+                    .suffix(" s"),
+            );
+            if self.auto_scan
+                && let Some(reason) = self.auto_scan_paused_reason(ui.ctx())
+            {
+                ui.weak(format!("(paused: {reason})"));
+            }
+        });
+
+        ui.separator();
+        ui.label("Connect");
+        ui.horizontal(|ui| {
+            ui.label("SSID");
+            ui.text_edit_singleline(&mut self.connect_ssid);
+            if self.selected_network_is_enterprise() {
+                ui.label("Username");
+                ui.text_edit_singleline(&mut self.connect_username);
+            }
+            ui.label("Passphrase");
+            ui.add(egui::TextEdit::singleline(&mut self.connect_passphrase).password(true));
+            if ui.button("Connect").clicked() {
+                self.connect_to_selected_network();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Connect to hidden network…").clicked() {
+                self.hidden_ssid = Some(String::new());
+            }
+            if ui.button("Connect with WPS…").clicked() {
+                self.wps = Some(WpsDialog::default());
+            }
+            if self.is_pending(|c| matches!(c, Command::ConnectHidden { .. })) {
+                ui.spinner();
+            }
+        });
+
+        ui.separator();
+        self.draw_station_status(ui);
+
+        ui.separator();
+        self.draw_hidden_access_points(ui);
+
+        ui.separator();
+        ui.label("Visible Networks");
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("visible_networks_grid")
+                .num_columns(6)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (sort, title) in [
+                        (NetworkSort::Rank, "Rank"),
+                        (NetworkSort::Ssid, "SSID"),
+                        (NetworkSort::Security, "Security"),
+                        (NetworkSort::Signal, "Signal"),
+                    ] {
+                        let heading = egui::RichText::new(title).strong();
+                        if ui
+                            .selectable_label(self.network_sort == sort, heading)
+                            .on_hover_text(format!("Sort by {}", title.to_lowercase()))
+                            .clicked()
+                        {
+                            self.set_network_sort(sort);
+                        }
+                    }
+                    ui.strong("Connected");
+                    ui.strong("Action");
+                    ui.end_row();
+
+                    let selected_device = self.selected_device_path.clone();
+                    let networks = self.visible_networks.clone();
+                    for network in networks {
+                        if selected_device.is_some()
+                            && network.device_path.as_deref() != selected_device.as_deref()
+                        {
+                            continue;
+                        }
+
+                        ui.label(
+                            network
+                                .rank
+                                .map(|rank| (rank + 1).to_string())
+                                .unwrap_or_else(|| "-".to_string()),
+                        );
+                        let is_selected = self.connect_ssid == network.ssid;
+                        if ui.selectable_label(is_selected, &network.ssid).clicked() {
+                            self.connect_ssid = network.ssid.clone();
+                        }
+                        ui.label(&network.security);
+                        draw_signal_indicator(
+                            ui,
+                            network_signal(&self.signal_levels, &network),
+                        );
+                        ui.label(if network.connected { "yes" } else { "no" });
+                        let connecting = self.is_pending(|c| {
+                            matches!(c, Command::Connect { network_path, .. } if *network_path == network.path)
+                        });
+                        if connecting {
+                            ui.spinner();
+                        } else if network.connected {
+                            self.draw_disconnect_button(ui);
+                        } else if ui.button("Connect").clicked() {
+                            self.connect_ssid = network.ssid;
+                            self.connect_to_selected_network();
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn draw_saved_tab(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.refresh_all();
+            }
+        });
+
+        ui.separator();
+        ui.label("Saved Networks");
+
+        egui::ScrollArea::vertical()
+            .max_height(220.0)
+            .show(ui, |ui| {
+                egui::Grid::new("known_networks_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Name");
+                        ui.strong("Type");
+                        ui.strong("Action");
+                        ui.end_row();
+
+                        let known = self.known_networks.clone();
+                        for network in known {
+                            let is_selected =
+                                self.selected_known_path.as_deref() == Some(network.path.as_str());
+                            if ui.selectable_label(is_selected, &network.name).clicked() {
+                                self.select_known_network(&network);
+                            }
+                            ui.label(network.network_type.clone());
+                            let forgetting = self.is_pending(|c| {
+                                matches!(c, Command::Forget { known_path, .. } if *known_path == network.path)
+                            });
+                            ui.horizontal(|ui| {
+                                if forgetting {
+                                    ui.spinner();
+                                } else if ui.button("Forget").clicked() {
+                                    self.forget_known_network(&network.path, &network.name);
+                                }
+                                if ui
+                                    .add_enabled(
+                                        network.network_type != "8021x",
+                                        egui::Button::new("Share"),
+                                    )
+                                    .on_disabled_hover_text("Enterprise networks cannot be shared")
+                                    .clicked()
+                                {
+                                    self.open_share(
+                                        &network.name,
+                                        &network.network_type,
+                                        network.hidden == Some(true),
+                                    );
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if self.selected_known_path.is_some() {
+            ui.separator();
+            if let Some(autoconnect) = self.selected_known_autoconnect {
+                let mut value = autoconnect;
+                if ui.checkbox(&mut value, "AutoConnect").changed() {
+                    self.set_known_autoconnect(value);
+                }
+            } else {
+                ui.label("AutoConnect: unavailable");
+            }
+
+            ui.add(
+                egui::TextEdit::multiline(&mut self.selected_known_details)
+                    .desired_rows(8)
+                    .interactive(false),
+            );
+        }
+    }
+
+    fn draw_hotspot_tab(&mut self, ui: &mut egui::Ui) {
+        let Some(device) = self.selected_device().cloned() else {
+            ui.label("Select a device first");
+            return;
+        };
+
+        if device.mode != DeviceMode::AccessPoint {
+            ui.heading(format!(
+                "{} is in {} mode",
+                device.name,
+                device.mode.label().to_lowercase()
+            ));
+            ui.label("Running a hotspot needs access point mode.");
+            ui.add_space(6.0);
+            self.draw_switch_mode_button(ui, &device, DeviceMode::AccessPoint);
+            return;
+        }
+
+        let access_point = self.selected_access_point().cloned().unwrap_or_default();
+        let busy = self.is_pending(|c| {
+            matches!(
+                c,
+                Command::StartAccessPoint { .. }
+                    | Command::StartAccessPointProfile { .. }
+                    | Command::StopAccessPoint { .. }
+            )
+        });
+
+        ui.heading(if access_point.started {
+            format!(
+                "Hotspot `{}` is running",
+                access_point.name.as_deref().unwrap_or("-")
+            )
+        } else {
+            "Hotspot is stopped".to_string()
+        });
+
+        egui::Grid::new("access_point_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Started");
+                ui.label(if access_point.started { "yes" } else { "no" });
+                ui.end_row();
+                ui.label("Name");
+                ui.label(access_point.name.as_deref().unwrap_or("-"));
+                ui.end_row();
+                ui.label("Frequency");
+                ui.label(
+                    access_point
+                        .frequency
+                        .map(|f| format!("{f} MHz"))
+                        .unwrap_or_else(|| "-".to_string()),
+                );
+                ui.end_row();
+                ui.label("Pairwise ciphers");
+                ui.label(if access_point.pairwise_ciphers.is_empty() {
+                    "-".to_string()
+                } else {
+                    access_point.pairwise_ciphers.join(", ")
+                });
+                ui.end_row();
+            });
+
+        ui.separator();
+        if busy {
+            ui.spinner();
+            return;
+        }
+
+        if access_point.started {
+            if ui.button("Stop hotspot").clicked() {
+                self.submit(Command::StopAccessPoint {
+                    device_path: device.path.clone(),
+                });
+            }
+            return;
+        }
+
+        ui.strong("Start hotspot");
+        egui::Grid::new("hotspot_start_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("SSID");
+                ui.text_edit_singleline(&mut self.hotspot_ssid);
+                ui.end_row();
+                ui.label("Passphrase");
+                ui.add(egui::TextEdit::singleline(&mut self.hotspot_passphrase).password(true));
+                ui.end_row();
+            });
+        if ui.button("Start").clicked() {
+            self.start_hotspot(device.path.clone());
+        }
+
+        ui.add_space(6.0);
+        ui.strong("Start from profile");
+        ui.horizontal(|ui| {
+            if self.ap_profiles.is_empty() {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.hotspot_profile).hint_text("profile name"),
+                );
+            } else {
+                egui::ComboBox::from_id_salt("ap_profile_selector")
+                    .selected_text(self.hotspot_profile.as_str())
+                    .show_ui(ui, |ui| {
+                        for profile in &self.ap_profiles {
+                            ui.selectable_value(
+                                &mut self.hotspot_profile,
+                                profile.clone(),
+                                profile,
+                            );
+                        }
+                    });
+            }
+            if ui.button("Start profile").clicked() {
+                self.start_hotspot_profile(device.path.clone());
+            }
+        });
+        ui.small("Profiles are read from /var/lib/iwd/ap/<name>.ap");
+    }
+
+    fn start_ad_hoc(&mut self, device_path: String) {
+        let Some(ssid) = non_empty(&self.ad_hoc_ssid) else {
+            self.set_status("Enter an SSID for the ad-hoc network");
+            return;
+        };
+        let passphrase = if self.ad_hoc_open {
+            None
+        } else if (8..=63).contains(&self.ad_hoc_passphrase.chars().count()) {
+            Some(self.ad_hoc_passphrase.clone())
+        } else {
+            self.set_status("The ad-hoc passphrase must be 8 to 63 characters");
+            return;
+        };
+
+        self.submit(Command::StartAdHoc {
+            device_path,
+            ssid,
+            passphrase,
+        });
+    }
+
+    /// Replaces the station UI while the selected device is in ad-hoc mode.
+    fn draw_ad_hoc_panel(&mut self, ui: &mut egui::Ui, device: &DeviceInfo) {
+        let ad_hoc = self
+            .ad_hocs
+            .iter()
+            .find(|a| a.path == device.path)
+            .cloned()
+            .unwrap_or_default();
+        let busy = self
+            .is_pending(|c| matches!(c, Command::StartAdHoc { .. } | Command::StopAdHoc { .. }));
+
+        ui.heading(if ad_hoc.started {
+            format!("Ad-hoc network running on {}", device.name)
+        } else {
+            format!("{} is in ad-hoc mode", device.name)
+        });
+
+        if ad_hoc.started {
+            ui.horizontal(|ui| {
+                if busy {
+                    ui.spinner();
+                } else if ui.button("Stop").clicked() {
+                    self.submit(Command::StopAdHoc {
+                        device_path: device.path.clone(),
+                    });
+                }
+            });
+
+            ui.separator();
+            ui.label(format!(
+                "Connected peers ({})",
+                ad_hoc.connected_peers.len()
+            ));
+            egui::ScrollArea::vertical()
+                .id_salt("ad_hoc_peers_scroll")
+                .max_height(220.0)
+                .show(ui, |ui| {
+                    if ad_hoc.connected_peers.is_empty() {
+                        ui.label("No peers yet");
+                    }
+                    for peer in &ad_hoc.connected_peers {
+                        ui.monospace(peer);
+                    }
+                });
+            return;
+        }
+
+        egui::Grid::new("ad_hoc_start_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("SSID");
+                ui.text_edit_singleline(&mut self.ad_hoc_ssid);
+                ui.end_row();
+                ui.label("Passphrase");
+                ui.add_enabled(
+                    !self.ad_hoc_open,
+                    egui::TextEdit::singleline(&mut self.ad_hoc_passphrase).password(true),
+                );
+                ui.end_row();
+            });
+        ui.checkbox(&mut self.ad_hoc_open, "Open network (no passphrase)");
+
+        ui.horizontal(|ui| {
+            if busy {
+                ui.spinner();
+            } else if ui.button("Start").clicked() {
+                self.start_ad_hoc(device.path.clone());
+            }
+            self.draw_switch_mode_button(ui, device, DeviceMode::Station);
+        });
+    }
+
+    fn load_dpp_uri_file(&mut self) {
+        let Some(path) = non_empty(&self.dpp_uri_file) else {
+            self.set_status("Enter the path of a file containing a DPP URI");
+            return;
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => match contents
+                .split_whitespace()
+                .find(|token| token.starts_with("DPP:"))
+            {
+                Some(uri) => {
+                    self.dpp_peer_uri = uri.to_string();
+                    self.set_status(format!("Loaded DPP URI from {path}"));
+                }
+                None => self.set_status(format!("No DPP URI found in {path}")),
+            },
+            Err(err) => self.set_status(format!("Failed to read {path}: {err}")),
+        }
+    }
+
+    fn configure_dpp_enrollee(&mut self, device_path: String) {
+        let uri = self.dpp_peer_uri.trim().to_string();
+        if !uri.starts_with("DPP:") {
+            self.set_status("Paste the enrollee's URI, it starts with `DPP:`");
+            return;
+        }
+
+        self.submit(Command::DppConfigureEnrollee { device_path, uri });
+    }
+
+    fn draw_easy_connect_tab(&mut self, ui: &mut egui::Ui) {
+        let Some(device) = self.selected_device().cloned() else {
+            ui.label("Select a device first");
+            return;
+        };
+
+        if device.mode != DeviceMode::Station {
+            ui.heading(format!(
+                "{} is in {} mode",
+                device.name,
+                device.mode.label().to_lowercase()
+            ));
+            ui.label("Wi-Fi Easy Connect needs station mode.");
+            ui.add_space(6.0);
+            self.draw_switch_mode_button(ui, &device, DeviceMode::Station);
+            return;
+        }
+
+        let dpp = self
+            .dpps
+            .iter()
+            .find(|d| d.path == device.path)
+            .cloned()
+            .unwrap_or_default();
+        let busy = self.is_pending(|c| {
+            matches!(
+                c,
+                Command::DppStartEnrollee { .. }
+                    | Command::DppStartConfigurator { .. }
+                    | Command::DppConfigureEnrollee { .. }
+                    | Command::DppStop { .. }
+            )
+        });
+
+        if dpp.started {
+            ui.heading(match dpp.role.as_deref() {
+                Some("configurator") => "Provisioning another device",
+                _ => "Waiting for a configurator",
+            });
+            if let Some(uri) = dpp.uri.as_deref() {
+                ui.label("Scan this code with the other device:");
+                draw_qr_code(ui, uri, 240.0);
+                ui.horizontal(|ui| {
+                    ui.monospace(uri);
+                    if ui.small_button("Copy").clicked() {
+                        ui.ctx().copy_text(uri.to_string());
+                    }
+                });
+            }
+            if busy {
+                ui.spinner();
+            } else if ui.button("Stop").clicked() {
+                self.submit(Command::DppStop {
+                    device_path: device.path.clone(),
+                });
+            }
+            return;
+        }
+
+        if busy {
+            ui.spinner();
+            return;
+        }
+
+        ui.heading("Join a network");
+        ui.label("Show a QR code to scan from a configurator that is already on the network.");
+        if ui.button("Start enrollee").clicked() {
+            self.submit(Command::DppStartEnrollee {
+                device_path: device.path.clone(),
+            });
+        }
+
+        ui.separator();
+        ui.heading("Pair with a shared code");
+        ui.label("For devices without a camera: both sides enter the same short code.");
+        if self.pkex_session().is_some_and(|p| p.started) {
+            ui.label("A shared code session is running.");
+        }
+        if ui.button("Shared code pairing…").clicked() {
+            self.pkex = Some(PkexDialog::default());
+        }
+
+        ui.separator();
+        ui.heading("Share this network");
+        let Some(network) = self.connected_network().cloned() else {
+            ui.label("Connect to a network first to provision other devices with it.");
+            return;
+        };
+        ui.label(format!(
+            "Provision a phone or other device with `{}`.",
+            network.ssid
+        ));
+        if ui.button("Show configurator QR code").clicked() {
+            self.submit(Command::DppStartConfigurator {
+                device_path: device.path.clone(),
+            });
+        }
+
+        ui.add_space(6.0);
+        ui.label("Or paste the other device's DPP URI:");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.dpp_peer_uri)
+                .desired_rows(2)
+                .hint_text("DPP:…"),
+        );
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.dpp_uri_file).hint_text("file path"));
+            if ui.button("Load").clicked() {
+                self.load_dpp_uri_file();
             }
-            if ui.button("Refresh").clicked() {
-                self.refresh_all();
+        });
+        if ui.button("Configure enrollee").clicked() {
+            self.configure_dpp_enrollee(device.path.clone());
+        }
+    }
+
+    fn pkex_session(&self) -> Option<&PkexInfo> {
+        let path = self.selected_device_path.as_deref()?;
+        self.pkexes.iter().find(|p| p.path == path)
+    }
+
+    fn start_pkex(&mut self) {
+        let Some(device_path) = self.selected_device_path.clone() else {
+            self.set_status("Select a device first");
+            return;
+        };
+        let Some(pkex) = self.pkex.as_ref() else {
+            return;
+        };
+
+        if pkex.role == PkexRole::Configurator && pkex.use_agent {
+            self.submit(Command::PkexStartConfigurator { device_path });
+            return;
+        }
+        let Some(code) = non_empty(&pkex.code) else {
+            self.set_status("Enter the shared code");
+            return;
+        };
+        let identifier = non_empty(&pkex.identifier);
+        self.submit(match pkex.role {
+            PkexRole::Enrollee => Command::PkexStartEnrollee {
+                device_path,
+                code,
+                identifier,
+            },
+            PkexRole::Configurator => Command::PkexConfigureEnrollee {
+                device_path,
+                code,
+                identifier,
+            },
+        });
+    }
+
+    fn draw_pkex_dialog(&mut self, ctx: &egui::Context) {
+        if self.pkex.is_none() {
+            return;
+        }
+
+        let session = self.pkex_session().cloned().unwrap_or_default();
+        let busy = self.is_pending(|c| {
+            matches!(
+                c,
+                Command::PkexStartEnrollee { .. }
+                    | Command::PkexConfigureEnrollee { .. }
+                    | Command::PkexStartConfigurator { .. }
+                    | Command::PkexStop { .. }
+            )
+        });
+        let connected = self.connected_network().map(|network| network.ssid.clone());
+        let Some(pkex) = self.pkex.as_mut() else {
+            return;
+        };
+
+        let mut start = false;
+        let mut stop = false;
+        let mut dismiss = false;
+        let modal = egui::Modal::new(egui::Id::new("pkex_dialog")).show(ctx, |ui| {
+            ui.heading("Shared code pairing");
+
+            if session.started {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(match session.role.as_deref() {
+                        Some("configurator") => "Waiting for enrollees…",
+                        _ => "Waiting for the configurator…",
+                    });
+                });
+                ui.horizontal(|ui| {
+                    if busy {
+                        ui.spinner();
+                    } else if ui.button("Stop").clicked() {
+                        stop = true;
+                    }
+                    if ui.button("Close").clicked() {
+                        dismiss = true;
+                    }
+                });
+                return;
             }
+
+            ui.radio_value(&mut pkex.role, PkexRole::Enrollee, "Join a network");
+            ui.radio_value(
+                &mut pkex.role,
+                PkexRole::Configurator,
+                "Share the current network",
+            );
+            if pkex.role == PkexRole::Configurator {
+                match connected.as_deref() {
+                    Some(ssid) => ui.label(format!("Enrollees will be given `{ssid}`.")),
+                    None => ui.label("Connect to a network first."),
+                };
+                ui.checkbox(
+                    &mut pkex.use_agent,
+                    "Ask for each device's code as it shows up",
+                );
+            }
+
+            let typed = !(pkex.role == PkexRole::Configurator && pkex.use_agent);
+            ui.add_enabled_ui(typed, |ui| {
+                egui::Grid::new("pkex_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Code");
+                    ui.text_edit_singleline(&mut pkex.code);
+                    ui.end_row();
+                    ui.label("Identifier");
+                    ui.add(egui::TextEdit::singleline(&mut pkex.identifier).hint_text("optional"));
+                    ui.end_row();
+                });
+            });
+
+            let can_start = pkex.role == PkexRole::Enrollee || connected.is_some();
+            ui.horizontal(|ui| {
+                if busy {
+                    ui.spinner();
+                } else if ui
+                    .add_enabled(can_start, egui::Button::new("Start"))
+                    .clicked()
+                {
+                    start = true;
+                }
+                if ui.button("Close").clicked() {
+                    dismiss = true;
+                }
+            });
         });
 
-        ui.separator();
-        ui.label("Connect");
-        ui.horizontal(|ui| {
-            ui.label("SSID");
-            ui.text_edit_singleline(&mut self.connect_ssid);
-            ui.label("Passphrase");
-            ui.add(egui::TextEdit::singleline(&mut self.connect_passphrase).password(true));
-            if ui.button("Connect").clicked() {
-                self.connect_to_selected_network();
+        if start {
+            self.start_pkex();
+        } else if stop {
+            if let Some(device_path) = self.selected_device_path.clone() {
+                self.submit(Command::PkexStop { device_path });
+            }
+        } else if dismiss || modal.should_close() {
+            self.pkex = None;
+        }
+    }
+
+    fn draw_shared_code_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = self.shared_code_prompt.as_mut() else {
+            return;
+        };
+
+        let mut submit = false;
+        let mut dismiss = false;
+        let modal = egui::Modal::new(egui::Id::new("shared_code_prompt")).show(ctx, |ui| {
+            ui.heading("Shared code required");
+            if prompt.request.identifier.is_empty() {
+                ui.label("A device wants to join. Enter the code shown on it.");
+            } else {
+                ui.label(format!(
+                    "Device `{}` wants to join. Enter the code shown on it.",
+                    prompt.request.identifier
+                ));
             }
+            ui.horizontal(|ui| {
+                ui.label("Code");
+                let field = ui.text_edit_singleline(&mut prompt.code);
+                if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submit = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("OK").clicked() {
+                    submit = true;
+                }
+                if ui.button("Reject").clicked() {
+                    dismiss = true;
+                }
+            });
         });
 
-        ui.separator();
-        ui.label("Visible Networks");
+        if submit && prompt.code.trim().is_empty() {
+            self.set_status("Shared code cannot be empty");
+        } else if submit {
+            if let Some(prompt) = self.shared_code_prompt.take() {
+                self.set_status("Sent shared code");
+                prompt.request.respond(prompt.code.trim().to_string());
+            }
+        } else if dismiss || modal.should_close() {
+            self.shared_code_prompt = None;
+            self.set_status("Shared code request rejected");
+        }
+    }
+
+    fn draw_diagnostics_tab(&mut self, ui: &mut egui::Ui) {
+        let connected = self
+            .selected_station()
+            .is_some_and(|station| station.state == StationState::Connected);
         egui::ScrollArea::vertical().show(ui, |ui| {
-            egui::Grid::new("visible_networks_grid")
-                .num_columns(6)
-                .striped(true)
-                .show(ui, |ui| {
-                    ui.strong("SSID");
-                    ui.strong("Security");
-                    ui.strong("Signal");
-                    ui.strong("Connected");
-                    ui.strong("Action");
+            if connected {
+                self.draw_link_details(ui);
+            } else {
+                ui.label("Diagnostics are available while connected.");
+            }
+            ui.separator();
+            self.draw_link_history(ui);
+        });
+    }
+
+    fn draw_link_details(&self, ui: &mut egui::Ui) {
+        if let Some(error) = self.diagnostics_error.as_deref() {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+            return;
+        }
+        let Some(diagnostics) = self.diagnostics.as_ref() else {
+            ui.spinner();
+            return;
+        };
+
+        let text = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        let bitrate =
+            |value: Option<u32>| text(value.map(|b| format!("{:.1} Mbit/s", bitrate_mbps(b))));
+        let rows = [
+            ("Connected BSS", text(diagnostics.connected_bss.clone())),
+            (
+                "Frequency",
+                text(diagnostics.frequency.map(|f| format!("{f} MHz"))),
+            ),
+            ("Channel", text(diagnostics.channel.map(|c| c.to_string()))),
+            ("Security", text(diagnostics.security.clone())),
+            ("RSSI", text(diagnostics.rssi.map(|r| format!("{r} dBm")))),
+            (
+                "Average RSSI",
+                text(diagnostics.average_rssi.map(|r| format!("{r} dBm"))),
+            ),
+            ("RX bitrate", bitrate(diagnostics.rx_bitrate)),
+            ("RX mode", text(diagnostics.rx_mode.clone())),
+            ("RX MCS", text(diagnostics.rx_mcs.map(|m| m.to_string()))),
+            ("TX bitrate", bitrate(diagnostics.tx_bitrate)),
+            ("TX mode", text(diagnostics.tx_mode.clone())),
+            ("TX MCS", text(diagnostics.tx_mcs.map(|m| m.to_string()))),
+            (
+                "Inactive time",
+                text(diagnostics.inactive_time.map(|t| format!("{t} ms"))),
+            ),
+        ];
+
+        ui.heading("Link diagnostics");
+        egui::Grid::new("diagnostics_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Field");
+                ui.strong("Value");
+                ui.end_row();
+                for (field, value) in rows {
+                    ui.label(field);
+                    ui.monospace(value);
                     ui.end_row();
+                }
+            });
+        ui.small(format!(
+            "Updated every {} s",
+            DIAGNOSTICS_INTERVAL.as_secs()
+        ));
+    }
 
-                    let selected_device = self.selected_device_path.clone();
-                    let networks = self.visible_networks.clone();
-                    for network in networks {
-                        if selected_device.is_some()
-                            && network.device_path.as_deref() != selected_device.as_deref()
-                        {
-                            continue;
-                        }
+    fn draw_link_history(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("History");
+            if ui
+                .add_enabled(self.link_history.is_some(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.link_history = None;
+            }
+            ui.checkbox(&mut self.record_link_history, "Record in background")
+                .on_hover_text("Keep sampling while other tabs are open");
+        });
 
-                        let is_selected = self.connect_ssid == network.ssid;
-                        if ui.selectable_label(is_selected, &network.ssid).clicked() {
-                            self.connect_ssid = network.ssid.clone();
-                        }
-                        ui.label(network.security);
-                        ui.label(network.signal);
-                        ui.label(if network.connected { "yes" } else { "no" });
-                        if ui.button("Connect").clicked() {
-                            self.connect_ssid = network.ssid;
-                            self.connect_to_selected_network();
-                        }
-                        ui.end_row();
-                    }
-                });
+        let selected_path = self.selected_station().map(|station| station.path.as_str());
+        let Some(history) = self
+            .link_history
+            .as_ref()
+            .filter(|history| Some(history.device_path.as_str()) == selected_path)
+        else {
+            ui.label("No samples recorded yet.");
+            return;
+        };
+
+        let roam_color = ui.visuals().warn_fg_color;
+        let disconnect_color = ui.visuals().error_fg_color;
+        let markers = |plot_ui: &mut PlotUi<'_>| {
+            for (at, event) in &history.events {
+                let x = history.elapsed(*at);
+                let marker = match event {
+                    LinkEvent::Roamed => VLine::new("Roam", x).color(roam_color),
+                    LinkEvent::Disconnected => VLine::new("Disconnect", x).color(disconnect_color),
+                };
+                plot_ui.vline(marker);
+            }
+        };
+
+        ui.label("Signal");
+        link_plot("link_rssi", "dBm").show(ui, |plot_ui| {
+            plot_ui.line(Line::new("RSSI", history.series(|s| s.rssi.map(f64::from))));
+            markers(plot_ui);
+        });
+        ui.label("Bitrate");
+        link_plot("link_bitrate", "Mbit/s").show(ui, |plot_ui| {
+            plot_ui.line(Line::new(
+                "RX",
+                history.series(|s| s.rx_bitrate.map(|b| f64::from(bitrate_mbps(b)))),
+            ));
+            plot_ui.line(Line::new(
+                "TX",
+                history.series(|s| s.tx_bitrate.map(|b| f64::from(bitrate_mbps(b)))),
+            ));
+            markers(plot_ui);
+        });
+        ui.label("Frequency");
+        link_plot("link_frequency", "MHz").show(ui, |plot_ui| {
+            plot_ui.line(Line::new(
+                "Frequency",
+                history.series(|s| s.frequency.map(f64::from)),
+            ));
+            markers(plot_ui);
         });
     }
 
-    fn draw_saved_tab(&mut self, ui: &mut egui::Ui) {
+    fn draw_devices_tab(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             if ui.button("Refresh").clicked() {
                 self.refresh_all();
@@ -314,61 +2770,124 @@ impl IwdGuiApp {
         });
 
         ui.separator();
-        ui.label("Saved Networks");
+        ui.label("Adapters");
 
         egui::ScrollArea::vertical()
-            .max_height(220.0)
+            .id_salt("adapters_scroll")
+            .max_height(180.0)
             .show(ui, |ui| {
-                egui::Grid::new("known_networks_grid")
-                    .num_columns(4)
+                egui::Grid::new("adapters_grid")
+                    .num_columns(5)
                     .striped(true)
                     .show(ui, |ui| {
                         ui.strong("Name");
-                        ui.strong("Type");
-                        ui.strong("Action");
+                        ui.strong("Model");
+                        ui.strong("Vendor");
+                        ui.strong("Powered");
+                        ui.strong("Supported Modes");
                         ui.end_row();
 
-                        let known = self.known_networks.clone();
-                        for network in known {
-                            let is_selected =
-                                self.selected_known_path.as_deref() == Some(network.path.as_str());
-                            if ui.selectable_label(is_selected, &network.name).clicked() {
-                                self.select_known_network(&network);
-                            }
-                            ui.label(network.network_type.clone());
-                            if ui.button("Forget").clicked() {
-                                self.forget_known_network(&network.path, &network.name);
+                        for adapter in &self.adapters {
+                            let is_selected = self.selected_adapter_path.as_deref()
+                                == Some(adapter.path.as_str());
+                            if ui.selectable_label(is_selected, &adapter.name).clicked() {
+                                self.selected_adapter_path = Some(adapter.path.clone());
                             }
+                            ui.label(adapter.model.as_deref().unwrap_or("-"));
+                            ui.label(adapter.vendor.as_deref().unwrap_or("-"));
+                            ui.label(if adapter.powered { "yes" } else { "no" });
+                            ui.label(adapter.supported_modes.join(", "));
                             ui.end_row();
                         }
                     });
             });
 
-        if self.selected_known_path.is_some() {
-            ui.separator();
-            if let Some(autoconnect) = self.selected_known_autoconnect {
-                let mut value = autoconnect;
-                if ui.checkbox(&mut value, "AutoConnect").changed() {
-                    self.set_known_autoconnect(value);
-                }
-            } else {
-                ui.label("AutoConnect: unavailable");
+        let Some(adapter) = self
+            .selected_adapter_path
+            .as_deref()
+            .and_then(|path| self.adapters.iter().find(|a| a.path == path))
+            .cloned()
+        else {
+            if self.adapters.is_empty() {
+                ui.label("No adapters found");
             }
+            return;
+        };
 
-            ui.add(
-                egui::TextEdit::multiline(&mut self.selected_known_details)
-                    .desired_rows(8)
-                    .interactive(false),
-            );
+        ui.separator();
+        ui.heading(format!("Adapter {}", adapter.name));
+        egui::Grid::new("adapter_details_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Model");
+                ui.label(adapter.model.as_deref().unwrap_or("unknown"));
+                ui.end_row();
+                ui.label("Vendor");
+                ui.label(adapter.vendor.as_deref().unwrap_or("unknown"));
+                ui.end_row();
+                ui.label("Powered");
+                ui.label(if adapter.powered { "yes" } else { "no" });
+                ui.end_row();
+                ui.label("Supported modes");
+                ui.label(adapter.supported_modes.join(", "));
+                ui.end_row();
+                ui.label("Object");
+                ui.label(&adapter.path);
+                ui.end_row();
+            });
+
+        ui.add_space(6.0);
+        ui.strong("Devices");
+        let devices: Vec<DeviceInfo> = self
+            .devices
+            .iter()
+            .filter(|d| d.adapter_path.as_deref() == Some(adapter.path.as_str()))
+            .cloned()
+            .collect();
+        if devices.is_empty() {
+            ui.label(if adapter.powered {
+                "No devices on this adapter"
+            } else {
+                "Devices appear once the adapter is powered on"
+            });
+            return;
         }
+
+        egui::Grid::new("adapter_devices_grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Address");
+                ui.strong("Mode");
+                ui.strong("Powered");
+                ui.end_row();
+
+                for device in devices {
+                    ui.label(&device.name);
+                    ui.monospace(&device.address);
+                    self.draw_mode_selector(ui, &device);
+                    ui.label(if device.powered { "yes" } else { "no" });
+                    ui.end_row();
+                }
+            });
     }
 }
 
 impl eframe::App for IwdGuiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if !self.initialized {
-            self.initialized = true;
-            self.refresh_all();
+        self.apply_pending_events();
+        self.apply_pending_replies();
+        self.run_auto_scan(ctx);
+        self.run_diagnostics_poll(ctx);
+
+        if !self.iwd_running {
+            egui::TopBottomPanel::top("iwd_missing_banner").show(ctx, |ui| {
+                ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    "iwd is not running. Waiting for net.connman.iwd to appear on the system bus…",
+                );
+            });
         }
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -381,7 +2900,7 @@ impl eframe::App for IwdGuiApp {
                             ui.selectable_value(
                                 &mut self.selected_device_path,
                                 Some(device.path.clone()),
-                                &device.name,
+                                device.label(),
                             );
                         }
                     });
@@ -389,22 +2908,380 @@ impl eframe::App for IwdGuiApp {
                 if ui.button("Refresh Devices").clicked() {
                     self.refresh_all();
                 }
+
+                if let Some(device) = self.selected_device().cloned() {
+                    ui.label("Mode");
+                    self.draw_mode_selector(ui, &device);
+                }
+
+                self.draw_power_toggles(ui);
+
+                if let Some(network) = self.connected_network() {
+                    ui.separator();
+                    ui.label(format!("Connected to `{}`", network.ssid));
+                    self.draw_disconnect_button(ui);
+                }
             });
 
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.active_tab, ActiveTab::Networks, "Networks");
                 ui.selectable_value(&mut self.active_tab, ActiveTab::Saved, "Saved");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Hotspot, "Hotspot");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::EasyConnect, "Easy Connect");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Diagnostics, "Diagnostics");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Devices, "Devices");
             });
         });
 
         egui::TopBottomPanel::bottom("status_panel").show(ctx, |ui| {
-            ui.label(self.status_line.as_str());
+            let mut cancel = None;
+            // Background operations: diagnostics polls run every few seconds and
+            // would only flicker, agent registrations are not the user's doing.
+            let visible = self.pending.iter().filter(|op| {
+                !op.canceled
+                    && !matches!(
+                        op.command,
+                        Command::GetDiagnostics { .. } | Command::RegisterSignalLevelAgent { .. }
+                    )
+            });
+            for op in visible {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(op.command.label());
+                    if self.can_cancel(op) && ui.small_button("Cancel").clicked() {
+                        cancel = Some(op.id);
+                    }
+                });
+            }
+            if let Some(id) = cancel {
+                self.cancel_operation(id);
+            }
+            ui.horizontal(|ui| {
+                ui.label(self.status_line.as_str());
+                if self.retry_command.is_some()
+                    && ui.small_button("Retry").clicked()
+                    && let Some((command, queued)) = self.retry_command.take()
+                {
+                    self.dispatch(command, queued);
+                }
+            });
         });
 
         egui::CentralPanel::default().show(ctx, |ui| match self.active_tab {
-            ActiveTab::Networks => self.draw_networks_tab(ui),
+            ActiveTab::Networks => match self.selected_device().cloned() {
+                Some(device) if device.mode == DeviceMode::AdHoc => {
+                    self.draw_ad_hoc_panel(ui, &device)
+                }
+                Some(device) if device.mode != DeviceMode::Station => {
+                    self.draw_mode_panel(ui, &device)
+                }
+                _ => self.draw_networks_tab(ui),
+            },
             ActiveTab::Saved => self.draw_saved_tab(ui),
+            ActiveTab::Hotspot => self.draw_hotspot_tab(ui),
+            ActiveTab::EasyConnect => self.draw_easy_connect_tab(ui),
+            ActiveTab::Diagnostics => self.draw_diagnostics_tab(ui),
+            ActiveTab::Devices => self.draw_devices_tab(ui),
         });
+
+        self.draw_hidden_network_dialog(ctx);
+        self.draw_wps_dialog(ctx);
+        self.draw_share_window(ctx);
+        self.draw_pkex_dialog(ctx);
+        self.draw_shared_code_prompt(ctx);
+        self.draw_agent_prompt(ctx);
+    }
+}
+
+/// A fixed-view plot for the diagnostics history, with elapsed time as `m:ss`
+/// on the X axis. The plots share their X axis and cursor.
+fn link_plot(id: &str, unit: &str) -> Plot<'static> {
+    let group = egui::Id::new("link_history");
+    Plot::new(id)
+        .height(140.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .link_axis(group, [true, false])
+        .link_cursor(group, [true, false])
+        .x_axis_label("Time (m:ss)")
+        .y_axis_label(unit.to_string())
+        .x_axis_formatter(|mark: GridMark, _range: &RangeInclusive<f64>| {
+            let seconds = mark.value.max(0.0) as u64;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        })
+}
+
+/// The signal to show for a visible network. The connected one follows the
+/// signal level agent, the others the last scan.
+fn network_signal(
+    levels: &HashMap<String, SignalLevelRegistration>,
+    network: &VisibleNetwork,
+) -> Option<Signal> {
+    let level = network
+        .device_path
+        .as_ref()
+        .filter(|_| network.connected)
+        .and_then(|device_path| match levels.get(device_path) {
+            Some(SignalLevelRegistration::Registered(level)) => *level,
+            _ => None,
+        });
+    level
+        .map(Signal::Level)
+        .or(network.signal.map(Signal::Exact))
+}
+
+/// Whether a `GetDiagnostics` failure means the station has no
+/// `StationDiagnostic` interface, rather than a passing error.
+fn diagnostics_unsupported(err: &IwdError) -> bool {
+    match err {
+        IwdError::NotSupported => true,
+        IwdError::Other { name, .. } => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.UnknownMethod"
+                | "org.freedesktop.DBus.Error.UnknownInterface"
+        ),
+        _ => false,
+    }
+}
+
+/// Draws signal strength as four bars plus a quality percentage, with the dBm
+/// reading (or range, for agent levels) on hover.
+fn draw_signal_indicator(ui: &mut egui::Ui, signal: Option<Signal>) {
+    let Some(signal) = signal else {
+        ui.label("-");
+        return;
+    };
+
+    let quality = signal_quality(signal.floor());
+    let color = if quality >= 60 {
+        egui::Color32::from_rgb(60, 180, 75)
+    } else if quality >= 35 {
+        egui::Color32::from_rgb(230, 180, 40)
+    } else {
+        egui::Color32::from_rgb(220, 60, 50)
+    };
+    let lit_bars = usize::from(quality).div_ceil(25);
+
+    ui.horizontal(|ui| {
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(22.0, 14.0), egui::Sense::hover());
+        let painter = ui.painter();
+        let unlit = ui.visuals().widgets.inactive.bg_fill;
+        let bar_width = rect.width() / 4.0;
+        for bar in 0..4 {
+            let height = rect.height() * (bar + 1) as f32 / 4.0;
+            let left = rect.left() + bar as f32 * bar_width;
+            let bar_rect = egui::Rect::from_min_max(
+                egui::pos2(left + 1.0, rect.bottom() - height),
+                egui::pos2(left + bar_width - 1.0, rect.bottom()),
+            );
+            let fill = if bar < lit_bars { color } else { unlit };
+            painter.rect_filled(bar_rect, 1.0, fill);
+        }
+        let label = ui.label(format!("{quality}%"));
+
+        let tooltip = format!("{}, {quality}% quality", signal.describe());
+        response.on_hover_text(&tooltip);
+        label.on_hover_text(tooltip);
+    });
+}
+
+/// Draws `data` as a black-on-white QR code with a quiet zone, at most `size`
+/// points wide.
+fn draw_qr_code(ui: &mut egui::Ui, data: &str, size: f32) {
+    let code = match QrCode::new(data.as_bytes()) {
+        Ok(code) => code,
+        Err(err) => {
+            ui.label(format!("Cannot render QR code: {err}"));
+            return;
+        }
+    };
+
+    const QUIET_ZONE: usize = 4;
+    let width = code.width();
+    let modules = width + 2 * QUIET_ZONE;
+    // Whole-point modules keep the edges crisp enough for phone cameras.
+    let module = (size / modules as f32).floor().max(1.0);
+    let (rect, _) = ui.allocate_exact_size(
+        egui::Vec2::splat(module * modules as f32),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color != qrcode::Color::Dark {
+            continue;
+        }
+        let x = (index % width + QUIET_ZONE) as f32 * module;
+        let y = (index / width + QUIET_ZONE) as f32 * module;
+        painter.rect_filled(
+            egui::Rect::from_min_size(rect.min + egui::vec2(x, y), egui::Vec2::splat(module)),
+            0.0,
+            egui::Color32::BLACK,
+        );
+    }
+}
+
+fn describe_failure(command: &Command, err: &IwdError) -> String {
+    let action = match command {
+        Command::Refresh => "Failed to load state from iwd".to_string(),
+        Command::Scan { .. } => "Scan failed".to_string(),
+        Command::Connect { .. } => "Connection failed".to_string(),
+        Command::ConnectHidden { ssid, .. } => format!("Connecting to hidden `{ssid}` failed"),
+        Command::Disconnect { .. } => "Disconnect failed".to_string(),
+        Command::Forget { name, .. } => format!("Failed to forget `{name}`"),
+        Command::SetAutoConnect { .. } => "Failed to update AutoConnect".to_string(),
+        Command::SetAdapterPowered { .. } => "Failed to change adapter power".to_string(),
+        Command::SetDevicePowered { .. } => "Failed to change device power".to_string(),
+        Command::SetDeviceMode { mode, .. } => {
+            format!("Failed to switch to {} mode", mode.label().to_lowercase())
+        }
+        Command::StartAccessPoint { .. } | Command::StartAccessPointProfile { .. } => {
+            "Failed to start hotspot".to_string()
+        }
+        Command::StopAccessPoint { .. } => "Failed to stop hotspot".to_string(),
+        Command::StartAdHoc { .. } => "Failed to start ad-hoc network".to_string(),
+        Command::StopAdHoc { .. } => "Failed to stop ad-hoc network".to_string(),
+        Command::WpsPushButton { .. } | Command::WpsStartPin { .. } => {
+            "WPS connection failed".to_string()
+        }
+        Command::WpsGeneratePin { .. } => "Failed to generate a WPS PIN".to_string(),
+        Command::WpsCancel { .. } => "Failed to cancel WPS".to_string(),
+        Command::DppStartEnrollee { .. } | Command::DppStartConfigurator { .. } => {
+            "Failed to start DPP".to_string()
+        }
+        Command::DppConfigureEnrollee { .. } => "Failed to configure the enrollee".to_string(),
+        Command::DppStop { .. } => "Failed to stop DPP".to_string(),
+        Command::PkexStartEnrollee { .. }
+        | Command::PkexConfigureEnrollee { .. }
+        | Command::PkexStartConfigurator { .. } => {
+            "Failed to start shared code pairing".to_string()
+        }
+        Command::PkexStop { .. } => "Failed to stop shared code pairing".to_string(),
+        Command::GetDiagnostics { .. } => "Failed to read diagnostics".to_string(),
+        Command::RegisterAgent => "Failed to register agent".to_string(),
+        Command::RegisterSignalLevelAgent { .. } => {
+            "Failed to register signal level agent".to_string()
+        }
+    };
+
+    let hint = match (command, err) {
+        (Command::Connect { .. }, IwdError::Failed(_) | IwdError::InvalidFormat(_)) => {
+            Some("wrong password?")
+        }
+        (Command::ConnectHidden { .. }, IwdError::NotFound) => {
+            Some("no hidden access point answered to that SSID")
+        }
+        (Command::ConnectHidden { .. }, IwdError::Other { name, .. })
+            if name == "net.connman.iwd.Error.NotHidden" =>
+        {
+            Some("the SSID is visible, connect to it from the list instead")
+        }
+        (Command::Connect { .. }, IwdError::Aborted) => Some("the attempt was interrupted"),
+        (Command::Scan { .. }, IwdError::Busy | IwdError::InProgress) => {
+            Some("a scan is already in progress")
+        }
+        (Command::SetAdapterPowered { .. }, IwdError::Failed(_)) => {
+            Some("the radio may be blocked, check `rfkill list`")
+        }
+        (Command::SetDeviceMode { .. }, IwdError::NotSupported | IwdError::InvalidArguments(_)) => {
+            Some("the adapter does not support that mode")
+        }
+        (Command::StartAccessPoint { .. }, IwdError::InvalidArguments(_)) => {
+            Some("check the SSID and that the passphrase is 8 to 63 characters")
+        }
+        (
+            Command::StartAccessPointProfile { .. },
+            IwdError::NotFound | IwdError::InvalidArguments(_),
+        ) => Some("no such profile in /var/lib/iwd/ap"),
+        (
+            Command::StartAccessPoint { .. } | Command::StartAccessPointProfile { .. },
+            IwdError::AlreadyExists,
+        ) => Some("the hotspot is already running"),
+        (Command::StartAdHoc { .. }, IwdError::InvalidArguments(_)) => {
+            Some("check the SSID and that the passphrase is 8 to 63 characters")
+        }
+        (Command::StartAdHoc { .. }, IwdError::AlreadyExists) => {
+            Some("an ad-hoc network is already running")
+        }
+        (
+            Command::WpsPushButton { .. } | Command::WpsStartPin { .. },
+            IwdError::Other { name, .. },
+        ) if name.ends_with(".SessionOverlap") => {
+            Some("several access points are in push-button mode, try PIN mode")
+        }
+        (
+            Command::WpsPushButton { .. } | Command::WpsStartPin { .. },
+            IwdError::Other { name, .. },
+        ) if name.ends_with(".WalkTimerExpired") || name.ends_with(".NotReachable") => {
+            Some("no access point answered in time")
+        }
+        (Command::WpsPushButton { .. } | Command::WpsStartPin { .. }, IwdError::Timeout) => {
+            Some("no access point answered in time")
+        }
+        (
+            Command::DppStartConfigurator { .. } | Command::DppConfigureEnrollee { .. },
+            IwdError::NotConnected,
+        ) => Some("connect to a network first"),
+        (Command::DppConfigureEnrollee { .. }, IwdError::InvalidArguments(_)) => {
+            Some("that is not a valid DPP URI")
+        }
+        (
+            Command::DppStartEnrollee { .. }
+            | Command::DppStartConfigurator { .. }
+            | Command::DppConfigureEnrollee { .. },
+            IwdError::NotSupported,
+        ) => Some("the driver or network does not support DPP"),
+        (
+            Command::PkexConfigureEnrollee { .. } | Command::PkexStartConfigurator { .. },
+            IwdError::NotConnected,
+        ) => Some("connect to a network first"),
+        (
+            Command::PkexStartEnrollee { .. }
+            | Command::PkexConfigureEnrollee { .. }
+            | Command::PkexStartConfigurator { .. },
+            IwdError::NotSupported,
+        ) => Some("the driver or network does not support shared code pairing"),
+        (Command::GetDiagnostics { .. }, err) if diagnostics_unsupported(err) => {
+            Some("the driver does not report link diagnostics")
+        }
+        (Command::RegisterAgent, IwdError::AlreadyExists) => {
+            Some("another agent is already registered")
+        }
+        (Command::RegisterSignalLevelAgent { .. }, IwdError::AlreadyExists) => {
+            Some("another program already watches this station's signal")
+        }
+        (_, IwdError::Busy | IwdError::InProgress) => Some("iwd is busy, try again shortly"),
+        (_, IwdError::PermissionDenied) => Some("are you in the netdev group?"),
+        (_, IwdError::ServiceUnavailable) => Some("start it with `systemctl start iwd`"),
+        (_, IwdError::NotConfigured) => Some("the network has no saved profile"),
+        _ => None,
+    };
+
+    match hint {
+        Some(hint) => format!("{action}: {err} — {hint}"),
+        None => format!("{action}: {err}"),
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn upsert_by_path<T>(items: &mut Vec<T>, item: T, path: impl Fn(&T) -> &String) {
+    match items
+        .iter_mut()
+        .find(|existing| path(existing) == path(&item))
+    {
+        Some(existing) => *existing = item,
+        None => items.push(item),
     }
 }
 
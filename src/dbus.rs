@@ -1,297 +1,1461 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::thread;
 
-use zbus::DBusError;
-use zbus::blocking::{Connection, Proxy};
-use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::message::Type as MessageType;
+use zbus::names::BusName;
+use zbus::{DBusError, MatchRule, Message};
+use zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
 
-use crate::models::{DeviceInfo, KnownNetwork, VisibleNetwork};
+use crate::models::{
+    AccessPointInfo, AdHocInfo, AdapterInfo, DeviceInfo, DeviceMode, DppInfo, HiddenAccessPoint,
+    KnownNetwork, PkexInfo, SIGNAL_LEVELS, StationDiagnostics, StationInfo, StationState,
+    VisibleNetwork,
+};
 
 const IWD_SERVICE: &str = "net.connman.iwd";
 const OBJECT_MANAGER_IFACE: &str = "org.freedesktop.DBus.ObjectManager";
+const PROPERTIES_IFACE: &str = "org.freedesktop.DBus.Properties";
+const ADAPTER_IFACE: &str = "net.connman.iwd.Adapter";
 const DEVICE_IFACE: &str = "net.connman.iwd.Device";
 const STATION_IFACE: &str = "net.connman.iwd.Station";
+const STATION_DIAGNOSTIC_IFACE: &str = "net.connman.iwd.StationDiagnostic";
+const ACCESS_POINT_IFACE: &str = "net.connman.iwd.AccessPoint";
+const AD_HOC_IFACE: &str = "net.connman.iwd.AdHoc";
+const SIMPLE_CONFIGURATION_IFACE: &str = "net.connman.iwd.SimpleConfiguration";
+const DEVICE_PROVISIONING_IFACE: &str = "net.connman.iwd.DeviceProvisioning";
+const SHARED_CODE_PROVISIONING_IFACE: &str = "net.connman.iwd.SharedCodeDeviceProvisioning";
 const NETWORK_IFACE: &str = "net.connman.iwd.Network";
 const KNOWN_NETWORK_IFACE: &str = "net.connman.iwd.KnownNetwork";
 const AGENT_MANAGER_IFACE: &str = "net.connman.iwd.AgentManager";
 const AGENT_OBJECT_PATH: &str = "/com/github/datfooldive/iwd_gui/agent";
+const SHARED_CODE_AGENT_OBJECT_PATH: &str = "/com/github/datfooldive/iwd_gui/shared_code_agent";
+const SIGNAL_LEVEL_AGENT_OBJECT_PATH: &str = "/com/github/datfooldive/iwd_gui/signal_level_agent";
 
 type PropMap = HashMap<String, OwnedValue>;
 type InterfaceMap = HashMap<String, PropMap>;
 type ManagedObjects = HashMap<OwnedObjectPath, InterfaceMap>;
 
-#[derive(Debug)]
+/// Failure of an iwd D-Bus operation, keyed by the iwd (or bus) error name so
+/// callers can tell e.g. a wrong passphrase from a busy radio.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum IwdError {
+    /// `net.connman.iwd.Error.Busy`
+    Busy,
+    /// `net.connman.iwd.Error.Failed`, carrying iwd's message.
+    Failed(String),
+    /// `net.connman.iwd.Error.Aborted`
+    Aborted,
+    /// `net.connman.iwd.Error.InvalidArguments`
+    InvalidArguments(String),
+    /// `net.connman.iwd.Error.InvalidFormat`
+    InvalidFormat(String),
+    /// `net.connman.iwd.Error.AlreadyExists`
+    AlreadyExists,
+    /// `net.connman.iwd.Error.InProgress`
+    InProgress,
+    /// `net.connman.iwd.Error.NoAgent`
+    NoAgent,
+    /// `net.connman.iwd.Error.NotAvailable`
+    NotAvailable,
+    /// `net.connman.iwd.Error.NotConfigured`
+    NotConfigured,
+    /// `net.connman.iwd.Error.NotConnected`
+    NotConnected,
+    /// `net.connman.iwd.Error.NotFound`
+    NotFound,
+    /// `net.connman.iwd.Error.NotSupported` or `.NotImplemented`
+    NotSupported,
+    /// `net.connman.iwd.Error.PermissionDenied` or the bus refusing access.
+    PermissionDenied,
+    /// Nothing owns `net.connman.iwd` on the bus.
+    ServiceUnavailable,
+    /// iwd did not answer in time.
+    Timeout,
+    /// Any other named D-Bus error.
+    Other { name: String, message: String },
+    /// Transport-level failure talking to the bus.
+    Bus(String),
+    /// Local failure, e.g. a reply that did not have the expected shape.
+    Internal(String),
+}
+
+impl IwdError {
+    /// Whether repeating the same operation later has a reasonable chance to
+    /// succeed without the user changing anything.
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            IwdError::Busy
+                | IwdError::InProgress
+                | IwdError::Aborted
+                | IwdError::NotAvailable
+                | IwdError::Timeout
+        )
+    }
+
+    fn from_error_name(name: &str, message: String) -> Self {
+        let short = name.strip_prefix("net.connman.iwd.Error.");
+        match (short, name) {
+            (Some("Busy"), _) => IwdError::Busy,
+            (Some("Failed"), _) => IwdError::Failed(message),
+            (Some("Aborted"), _) => IwdError::Aborted,
+            (Some("InvalidArguments"), _) => IwdError::InvalidArguments(message),
+            (Some("InvalidFormat"), _) => IwdError::InvalidFormat(message),
+            (Some("AlreadyExists"), _) => IwdError::AlreadyExists,
+            (Some("InProgress"), _) => IwdError::InProgress,
+            (Some("NoAgent"), _) => IwdError::NoAgent,
+            (Some("NotAvailable"), _) => IwdError::NotAvailable,
+            (Some("NotConfigured"), _) => IwdError::NotConfigured,
+            (Some("NotConnected"), _) => IwdError::NotConnected,
+            (Some("NotFound"), _) => IwdError::NotFound,
+            (Some("NotSupported" | "NotImplemented"), _) => IwdError::NotSupported,
+            (Some("PermissionDenied"), _) | (_, "org.freedesktop.DBus.Error.AccessDenied") => {
+                IwdError::PermissionDenied
+            }
+            (
+                _,
+                "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner",
+            ) => IwdError::ServiceUnavailable,
+            (_, "org.freedesktop.DBus.Error.NoReply" | "org.freedesktop.DBus.Error.Timeout") => {
+                IwdError::Timeout
+            }
+            _ => IwdError::Other {
+                name: name.to_string(),
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for IwdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IwdError::Busy => write!(f, "iwd is busy"),
+            IwdError::Failed(message) if message.is_empty() => write!(f, "operation failed"),
+            IwdError::Failed(message) => write!(f, "operation failed: {message}"),
+            IwdError::Aborted => write!(f, "operation aborted"),
+            IwdError::InvalidArguments(message) => write!(f, "invalid arguments: {message}"),
+            IwdError::InvalidFormat(message) => write!(f, "invalid format: {message}"),
+            IwdError::AlreadyExists => write!(f, "already exists"),
+            IwdError::InProgress => write!(f, "operation already in progress"),
+            IwdError::NoAgent => write!(f, "no agent registered"),
+            IwdError::NotAvailable => write!(f, "not available"),
+            IwdError::NotConfigured => write!(f, "not configured"),
+            IwdError::NotConnected => write!(f, "not connected"),
+            IwdError::NotFound => write!(f, "not found"),
+            IwdError::NotSupported => write!(f, "not supported"),
+            IwdError::PermissionDenied => write!(f, "permission denied"),
+            IwdError::ServiceUnavailable => write!(f, "iwd is not running"),
+            IwdError::Timeout => write!(f, "iwd did not reply in time"),
+            IwdError::Other { name, message } => write!(f, "{name}: {message}"),
+            IwdError::Bus(message) => write!(f, "D-Bus error: {message}"),
+            IwdError::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for IwdError {}
+
+impl From<zbus::Error> for IwdError {
+    fn from(err: zbus::Error) -> Self {
+        match err {
+            zbus::Error::MethodError(name, message, _) => {
+                IwdError::from_error_name(name.as_str(), message.unwrap_or_default())
+            }
+            zbus::Error::FDO(err) => IwdError::from(*err),
+            other => IwdError::Bus(other.to_string()),
+        }
+    }
+}
+
+impl From<zbus::fdo::Error> for IwdError {
+    fn from(err: zbus::fdo::Error) -> Self {
+        match err {
+            zbus::fdo::Error::ZBus(err) => IwdError::from(err),
+            zbus::fdo::Error::AccessDenied(_) => IwdError::PermissionDenied,
+            zbus::fdo::Error::ServiceUnknown(_) | zbus::fdo::Error::NameHasNoOwner(_) => {
+                IwdError::ServiceUnavailable
+            }
+            zbus::fdo::Error::NoReply(_) | zbus::fdo::Error::Timeout(_) => IwdError::Timeout,
+            other => IwdError::Bus(other.to_string()),
+        }
+    }
+}
+
+/// Handle to the system bus. Cloning is cheap and shares the same underlying
+/// connection.
+#[derive(Clone, Debug)]
 pub(crate) struct IwdDbus {
     conn: Connection,
 }
 
+/// Incremental change pushed from the signal listener thread. Updates carry the
+/// full, current model for the object so the receiver can simply upsert by path.
+#[derive(Clone, Debug)]
+pub(crate) enum IwdEvent {
+    AdapterUpdated(AdapterInfo),
+    AdapterRemoved(String),
+    DeviceUpdated(DeviceInfo),
+    DeviceRemoved(String),
+    NetworkUpdated(VisibleNetwork),
+    NetworkRemoved(String),
+    KnownNetworkUpdated(KnownNetwork),
+    KnownNetworkRemoved(String),
+    StationUpdated(StationInfo),
+    StationRemoved(String),
+    AccessPointUpdated(AccessPointInfo),
+    AccessPointRemoved(String),
+    AdHocUpdated(AdHocInfo),
+    AdHocRemoved(String),
+    DppUpdated(DppInfo),
+    DppRemoved(String),
+    PkexUpdated(PkexInfo),
+    PkexRemoved(String),
+    /// `net.connman.iwd` gained an owner on the bus, e.g. after a restart.
+    ServiceAppeared,
+    /// `net.connman.iwd` lost its owner; all previously known objects are gone.
+    ServiceVanished,
+    /// iwd needs credentials from the user; see [`AgentRequest`].
+    AgentRequest(AgentRequest),
+    /// iwd withdrew the outstanding agent request.
+    AgentCanceled(String),
+    SharedCodeRequest(SharedCodeRequest),
+    SharedCodeCanceled(String),
+    /// The station's signal moved into another range of [`SIGNAL_LEVELS`].
+    SignalLevelChanged(String, u8),
+    /// iwd dropped the signal level agent of the station, e.g. because the
+    /// device went away.
+    SignalLevelAgentReleased(String),
+}
+
+/// Local mirror of iwd's object tree, kept current from ObjectManager and
+/// Properties signals so that partial property updates can be turned into
+/// complete models.
+///
+/// Signals and `GetManagedObjects` replies are handled on different threads,
+/// so a snapshot is merged into the cache instead of replacing it: whatever
+/// the signals already delivered is at least as recent.
 #[derive(Debug, Default)]
-struct AgentState {
-    passphrase: String,
+struct ObjectCache {
+    objects: ManagedObjects,
+    /// Unique bus name of the iwd instance the objects belong to.
+    owner: Option<String>,
+    /// Interfaces removed since `owner` took over, so that an older snapshot
+    /// cannot bring them back.
+    removed: HashSet<(OwnedObjectPath, String)>,
+}
+
+impl ObjectCache {
+    /// Fills the cache from the startup snapshot, unless the owner watcher has
+    /// already moved on to another iwd instance.
+    fn seed(&mut self, owner: Option<String>, objects: ManagedObjects) {
+        if self.owner.is_none() || self.owner == owner {
+            self.owner = owner;
+            self.merge(objects);
+        }
+    }
+
+    /// Forgets everything known about the previous iwd instance.
+    fn reset(&mut self, owner: Option<String>) {
+        self.objects.clear();
+        self.removed.clear();
+        self.owner = owner;
+    }
+
+    /// Adds the objects and interfaces of a `GetManagedObjects` reply that the
+    /// cache does not know yet.
+    fn merge(&mut self, objects: ManagedObjects) {
+        for (path, interfaces) in objects {
+            for (interface, props) in interfaces {
+                if self.removed.contains(&(path.clone(), interface.clone())) {
+                    continue;
+                }
+                self.objects
+                    .entry(path.clone())
+                    .or_default()
+                    .entry(interface)
+                    .or_insert(props);
+            }
+        }
+    }
+
+    fn handle_signal(&mut self, msg: &Message) -> Vec<IwdEvent> {
+        let header = msg.header();
+        let (Some(interface), Some(member), Some(sender_path)) =
+            (header.interface(), header.member(), header.path())
+        else {
+            return Vec::new();
+        };
+        if !matches!(
+            (interface.as_str(), member.as_str()),
+            (
+                OBJECT_MANAGER_IFACE,
+                "InterfacesAdded" | "InterfacesRemoved"
+            ) | (PROPERTIES_IFACE, "PropertiesChanged")
+        ) {
+            return Vec::new();
+        }
+        // The match rule names iwd by its well-known name, which zbus also
+        // lets the bus daemon's own signals (e.g. NameOwnerChanged) through.
+        let sender = header.sender().map(|sender| sender.to_string());
+        if sender
+            .as_ref()
+            .is_some_and(|sender| !sender.starts_with(':'))
+        {
+            return Vec::new();
+        }
+        // A signal from a new iwd instance can arrive before the owner watcher
+        // has seen the restart; nothing of the old instance is valid anymore.
+        if sender.is_some() && sender != self.owner {
+            self.reset(sender);
+        }
+        let body = msg.body();
+
+        match (interface.as_str(), member.as_str()) {
+            (OBJECT_MANAGER_IFACE, "InterfacesAdded") => {
+                match body.deserialize::<(OwnedObjectPath, InterfaceMap)>() {
+                    Ok((path, interfaces)) => self.interfaces_added(path, interfaces),
+                    Err(_) => Vec::new(),
+                }
+            }
+            (OBJECT_MANAGER_IFACE, "InterfacesRemoved") => {
+                match body.deserialize::<(OwnedObjectPath, Vec<String>)>() {
+                    Ok((path, interfaces)) => self.interfaces_removed(&path, &interfaces),
+                    Err(_) => Vec::new(),
+                }
+            }
+            (PROPERTIES_IFACE, "PropertiesChanged") => {
+                match body.deserialize::<(String, PropMap, Vec<String>)>() {
+                    Ok((interface, changed, invalidated)) => {
+                        let path = OwnedObjectPath::from(sender_path.to_owned());
+                        self.properties_changed(&path, &interface, changed, &invalidated)
+                    }
+                    Err(_) => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn interfaces_added(
+        &mut self,
+        path: OwnedObjectPath,
+        interfaces: InterfaceMap,
+    ) -> Vec<IwdEvent> {
+        let names: Vec<String> = interfaces.keys().cloned().collect();
+        for name in &names {
+            self.removed.remove(&(path.clone(), name.clone()));
+        }
+        self.objects
+            .entry(path.clone())
+            .or_default()
+            .extend(interfaces);
+        names
+            .iter()
+            .filter_map(|interface| self.object_event(&path, interface))
+            .collect()
+    }
+
+    fn interfaces_removed(
+        &mut self,
+        path: &OwnedObjectPath,
+        interfaces: &[String],
+    ) -> Vec<IwdEvent> {
+        self.removed.extend(
+            interfaces
+                .iter()
+                .map(|interface| (path.clone(), interface.clone())),
+        );
+        let Some(object) = self.objects.get_mut(path) else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        for interface in interfaces {
+            if object.remove(interface).is_none() {
+                continue;
+            }
+            let path_str = path.as_str().to_string();
+            match interface.as_str() {
+                ADAPTER_IFACE => events.push(IwdEvent::AdapterRemoved(path_str)),
+                DEVICE_IFACE => events.push(IwdEvent::DeviceRemoved(path_str)),
+                NETWORK_IFACE => events.push(IwdEvent::NetworkRemoved(path_str)),
+                KNOWN_NETWORK_IFACE => events.push(IwdEvent::KnownNetworkRemoved(path_str)),
+                STATION_IFACE => events.push(IwdEvent::StationRemoved(path_str)),
+                ACCESS_POINT_IFACE => events.push(IwdEvent::AccessPointRemoved(path_str)),
+                AD_HOC_IFACE => events.push(IwdEvent::AdHocRemoved(path_str)),
+                DEVICE_PROVISIONING_IFACE => events.push(IwdEvent::DppRemoved(path_str)),
+                SHARED_CODE_PROVISIONING_IFACE => events.push(IwdEvent::PkexRemoved(path_str)),
+                _ => {}
+            }
+        }
+
+        if object.is_empty() {
+            self.objects.remove(path);
+        }
+        events
+    }
+
+    fn properties_changed(
+        &mut self,
+        path: &OwnedObjectPath,
+        interface: &str,
+        changed: PropMap,
+        invalidated: &[String],
+    ) -> Vec<IwdEvent> {
+        let Some(props) = self
+            .objects
+            .get_mut(path)
+            .and_then(|object| object.get_mut(interface))
+        else {
+            return Vec::new();
+        };
+
+        props.extend(changed);
+        for name in invalidated {
+            props.remove(name);
+        }
+        self.object_event(path, interface).into_iter().collect()
+    }
+
+    fn object_event(&self, path: &OwnedObjectPath, interface: &str) -> Option<IwdEvent> {
+        let props = self.objects.get(path)?.get(interface)?;
+        let path = path.as_str();
+        match interface {
+            ADAPTER_IFACE => adapter_from_props(path, props)
+                .ok()
+                .map(IwdEvent::AdapterUpdated),
+            DEVICE_IFACE => device_from_props(path, props)
+                .ok()
+                .map(IwdEvent::DeviceUpdated),
+            NETWORK_IFACE => visible_network_from_props(path, props)
+                .ok()
+                .map(IwdEvent::NetworkUpdated),
+            KNOWN_NETWORK_IFACE => known_network_from_props(path, props)
+                .ok()
+                .map(IwdEvent::KnownNetworkUpdated),
+            STATION_IFACE => Some(IwdEvent::StationUpdated(station_from_props(path, props))),
+            ACCESS_POINT_IFACE => Some(IwdEvent::AccessPointUpdated(access_point_from_props(
+                path, props,
+            ))),
+            AD_HOC_IFACE => Some(IwdEvent::AdHocUpdated(ad_hoc_from_props(path, props))),
+            DEVICE_PROVISIONING_IFACE => Some(IwdEvent::DppUpdated(dpp_from_props(path, props))),
+            SHARED_CODE_PROVISIONING_IFACE => {
+                Some(IwdEvent::PkexUpdated(pkex_from_props(path, props)))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn prop<T>(props: &PropMap, name: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    props.get(name)?.try_clone().ok()?.try_into().ok()
+}
+
+fn adapter_from_props(path: &str, props: &PropMap) -> Result<AdapterInfo, IwdError> {
+    let name: String = prop(props, "Name")
+        .ok_or_else(|| IwdError::Internal(format!("Failed to read adapter name at {path}")))?;
+    Ok(AdapterInfo {
+        name,
+        model: prop(props, "Model"),
+        vendor: prop(props, "Vendor"),
+        powered: prop(props, "Powered").unwrap_or(false),
+        supported_modes: prop(props, "SupportedModes").unwrap_or_default(),
+        path: path.to_string(),
+    })
+}
+
+fn device_from_props(path: &str, props: &PropMap) -> Result<DeviceInfo, IwdError> {
+    let name: String = prop(props, "Name")
+        .ok_or_else(|| IwdError::Internal(format!("Failed to read device name at {path}")))?;
+    Ok(DeviceInfo {
+        name,
+        address: prop(props, "Address").unwrap_or_default(),
+        mode: prop::<String>(props, "Mode")
+            .map(|mode| DeviceMode::from_iwd(&mode))
+            .unwrap_or_default(),
+        powered: prop(props, "Powered").unwrap_or(false),
+        adapter_path: prop::<OwnedObjectPath>(props, "Adapter").map(|v| v.as_str().to_string()),
+        path: path.to_string(),
+    })
+}
+
+fn visible_network_from_props(path: &str, props: &PropMap) -> Result<VisibleNetwork, IwdError> {
+    let ssid: String = prop(props, "Name")
+        .ok_or_else(|| IwdError::Internal(format!("Failed to read network name at {path}")))?;
+    let security: String = prop(props, "Type").unwrap_or_else(|| "-".to_string());
+    let connected: bool = prop(props, "Connected").unwrap_or(false);
+    let device_path: Option<String> =
+        prop::<OwnedObjectPath>(props, "Device").map(|v| v.as_str().to_string());
+
+    Ok(VisibleNetwork {
+        ssid,
+        security,
+        signal: None,
+        rank: None,
+        connected,
+        path: path.to_string(),
+        device_path,
+    })
+}
+
+fn station_from_props(path: &str, props: &PropMap) -> StationInfo {
+    let state: String = prop(props, "State").unwrap_or_default();
+    StationInfo {
+        path: path.to_string(),
+        state: StationState::from_iwd(&state),
+        connected_network: prop::<OwnedObjectPath>(props, "ConnectedNetwork")
+            .map(|v| v.as_str().to_string()),
+        scanning: prop(props, "Scanning").unwrap_or(false),
+    }
+}
+
+fn access_point_from_props(path: &str, props: &PropMap) -> AccessPointInfo {
+    AccessPointInfo {
+        path: path.to_string(),
+        started: prop(props, "Started").unwrap_or(false),
+        name: prop(props, "Name"),
+        frequency: prop(props, "Frequency"),
+        pairwise_ciphers: prop(props, "PairwiseCiphers").unwrap_or_default(),
+    }
+}
+
+fn ad_hoc_from_props(path: &str, props: &PropMap) -> AdHocInfo {
+    AdHocInfo {
+        path: path.to_string(),
+        started: prop(props, "Started").unwrap_or(false),
+        connected_peers: prop(props, "ConnectedPeers").unwrap_or_default(),
+    }
+}
+
+fn dpp_from_props(path: &str, props: &PropMap) -> DppInfo {
+    DppInfo {
+        path: path.to_string(),
+        started: prop(props, "Started").unwrap_or(false),
+        role: prop(props, "Role"),
+        uri: prop(props, "URI"),
+    }
+}
+
+fn pkex_from_props(path: &str, props: &PropMap) -> PkexInfo {
+    PkexInfo {
+        path: path.to_string(),
+        started: prop(props, "Started").unwrap_or(false),
+        role: prop(props, "Role"),
+    }
+}
+
+/// The `a{sv}` argument of the PKEX start methods.
+fn pkex_args<'a>(code: &'a str, identifier: Option<&'a str>) -> HashMap<&'static str, Value<'a>> {
+    let mut args = HashMap::new();
+    args.insert("Code", Value::from(code));
+    if let Some(identifier) = identifier {
+        args.insert("Identifier", Value::from(identifier));
+    }
+    args
+}
+
+fn known_network_from_props(path: &str, props: &PropMap) -> Result<KnownNetwork, IwdError> {
+    let name: String = prop(props, "Name")
+        .ok_or_else(|| IwdError::Internal("Failed to read known network name".to_string()))?;
+    let network_type: String = prop(props, "Type").unwrap_or_else(|| "-".to_string());
+
+    Ok(KnownNetwork {
+        name,
+        network_type,
+        autoconnect: prop(props, "AutoConnect"),
+        hidden: prop(props, "Hidden"),
+        path: path.to_string(),
+    })
+}
+
+/// What iwd asked the agent for.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum AgentRequestKind {
+    Passphrase,
+    PrivateKeyPassphrase,
+    UserNameAndPassword,
+    UserPassword { user: String },
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AgentResponse {
+    pub(crate) user: String,
+    pub(crate) secret: String,
+}
+
+/// A pending agent callback waiting for the user. Dropping it without
+/// responding cancels the request towards iwd.
+#[derive(Clone, Debug)]
+pub(crate) struct AgentRequest {
+    pub(crate) kind: AgentRequestKind,
+    pub(crate) network_path: String,
+    reply: async_channel::Sender<AgentResponse>,
+}
+
+impl AgentRequest {
+    pub(crate) fn respond(self, response: AgentResponse) {
+        let _ = self.reply.try_send(response);
+    }
 }
 
-#[derive(Debug)]
 struct IwdAgent {
-    state: Arc<Mutex<AgentState>>,
+    events: Sender<IwdEvent>,
+    notify: Arc<dyn Fn() + Send + Sync>,
 }
 
 impl IwdAgent {
-    fn new(passphrase: String) -> Self {
-        Self {
-            state: Arc::new(Mutex::new(AgentState { passphrase })),
-        }
+    async fn ask(
+        &self,
+        kind: AgentRequestKind,
+        network: OwnedObjectPath,
+    ) -> Result<AgentResponse, AgentError> {
+        let (reply, response) = async_channel::bounded(1);
+        let request = AgentRequest {
+            kind,
+            network_path: network.as_str().to_string(),
+            reply,
+        };
+        self.events
+            .send(IwdEvent::AgentRequest(request))
+            .map_err(|_| AgentError::Canceled("user interface is gone".to_string()))?;
+        (self.notify)();
+
+        response
+            .recv()
+            .await
+            .map_err(|_| AgentError::Canceled("request dismissed by the user".to_string()))
     }
 
-    fn passphrase_or_cancel(&self) -> Result<String, AgentError> {
-        let state = self
-            .state
-            .lock()
-            .map_err(|_| AgentError::Failed("agent lock poisoned".to_string()))?;
-        if state.passphrase.trim().is_empty() {
-            Err(AgentError::Canceled("passphrase is empty".to_string()))
-        } else {
-            Ok(state.passphrase.clone())
-        }
+    fn dismiss(&self, reason: &str) {
+        let _ = self
+            .events
+            .send(IwdEvent::AgentCanceled(reason.to_string()));
+        (self.notify)();
     }
 }
 
 #[derive(Debug, DBusError)]
-#[zbus(prefix = "net.connman.iwd.Error")]
+#[zbus(prefix = "net.connman.iwd.Agent.Error")]
 enum AgentError {
     Canceled(String),
-    Failed(String),
     #[zbus(error)]
     ZBus(zbus::Error),
 }
 
 #[zbus::interface(name = "net.connman.iwd.Agent")]
 impl IwdAgent {
-    fn release(&self) {}
+    fn release(&self) {
+        self.dismiss("agent released");
+    }
 
-    fn cancel(&self, _reason: &str) {}
+    fn cancel(&self, reason: &str) {
+        self.dismiss(reason);
+    }
 
-    fn request_passphrase(&self, _network: OwnedObjectPath) -> Result<String, AgentError> {
-        self.passphrase_or_cancel()
+    async fn request_passphrase(&self, network: OwnedObjectPath) -> Result<String, AgentError> {
+        self.ask(AgentRequestKind::Passphrase, network)
+            .await
+            .map(|r| r.secret)
     }
 
-    fn request_private_key_passphrase(&self, _path: &str) -> Result<String, AgentError> {
-        self.passphrase_or_cancel()
+    async fn request_private_key_passphrase(
+        &self,
+        network: OwnedObjectPath,
+    ) -> Result<String, AgentError> {
+        self.ask(AgentRequestKind::PrivateKeyPassphrase, network)
+            .await
+            .map(|r| r.secret)
     }
 
-    fn request_user_name_and_password(
+    async fn request_user_name_and_password(
         &self,
-        _name: &str,
-        _service: &str,
+        network: OwnedObjectPath,
     ) -> Result<(String, String), AgentError> {
-        let pass = self.passphrase_or_cancel()?;
-        Ok(("".to_string(), pass))
+        self.ask(AgentRequestKind::UserNameAndPassword, network)
+            .await
+            .map(|r| (r.user, r.secret))
     }
 
-    fn request_user_password(&self, _name: &str, _service: &str) -> Result<String, AgentError> {
-        self.passphrase_or_cancel()
+    async fn request_user_password(
+        &self,
+        network: OwnedObjectPath,
+        user: String,
+    ) -> Result<String, AgentError> {
+        self.ask(AgentRequestKind::UserPassword { user }, network)
+            .await
+            .map(|r| r.secret)
     }
 }
 
-struct RegisteredAgent<'a> {
-    conn: &'a Connection,
+/// A pending `SharedCodeAgent.RequestSharedCode` call. Dropping it without
+/// responding cancels the request towards iwd.
+#[derive(Clone, Debug)]
+pub(crate) struct SharedCodeRequest {
+    /// The identifier the enrollee announced; may be empty.
+    pub(crate) identifier: String,
+    reply: async_channel::Sender<String>,
 }
 
-impl<'a> RegisteredAgent<'a> {
-    fn new(conn: &'a Connection, passphrase: &str) -> Result<Self, String> {
-        let object_server = conn.object_server();
-        let _ = object_server.remove::<IwdAgent, _>(AGENT_OBJECT_PATH);
-        object_server
-            .at(AGENT_OBJECT_PATH, IwdAgent::new(passphrase.to_string()))
-            .map_err(|e| e.to_string())?;
+impl SharedCodeRequest {
+    pub(crate) fn respond(self, code: String) {
+        let _ = self.reply.try_send(code);
+    }
+}
 
-        let manager = Proxy::new(conn, IWD_SERVICE, "/net/connman/iwd", AGENT_MANAGER_IFACE)
-            .map_err(|e| e.to_string())?;
-        let path = ObjectPath::try_from(AGENT_OBJECT_PATH)
-            .map_err(|e| format!("invalid agent path: {e}"))?;
-        let _: () = manager
-            .call("RegisterAgent", &(path))
-            .map_err(|e| e.to_string())?;
+/// Answers iwd's shared-code requests while the GUI runs a PKEX configurator
+/// that serves several enrollees.
+struct SharedCodeAgent {
+    events: Sender<IwdEvent>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+}
 
-        Ok(Self { conn })
+impl SharedCodeAgent {
+    fn dismiss(&self, reason: &str) {
+        let _ = self
+            .events
+            .send(IwdEvent::SharedCodeCanceled(reason.to_string()));
+        (self.notify)();
     }
 }
 
-impl Drop for RegisteredAgent<'_> {
-    fn drop(&mut self) {
-        if let Ok(manager) = Proxy::new(
-            self.conn,
-            IWD_SERVICE,
-            "/net/connman/iwd",
-            AGENT_MANAGER_IFACE,
-        ) {
-            if let Ok(path) = ObjectPath::try_from(AGENT_OBJECT_PATH) {
-                let _ = manager.call::<_, _, ()>("UnregisterAgent", &(path));
-            }
-        }
-        let _ = self
-            .conn
-            .object_server()
-            .remove::<IwdAgent, _>(AGENT_OBJECT_PATH);
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "net.connman.iwd.SharedCodeAgent.Error")]
+enum SharedCodeAgentError {
+    Canceled(String),
+    #[zbus(error)]
+    ZBus(zbus::Error),
+}
+
+#[zbus::interface(name = "net.connman.iwd.SharedCodeAgent")]
+impl SharedCodeAgent {
+    fn release(&self) {
+        self.dismiss("agent released");
+    }
+
+    fn cancel(&self, reason: &str) {
+        self.dismiss(reason);
+    }
+
+    async fn request_shared_code(
+        &self,
+        identifier: String,
+    ) -> Result<String, SharedCodeAgentError> {
+        let (reply, code) = async_channel::bounded(1);
+        self.events
+            .send(IwdEvent::SharedCodeRequest(SharedCodeRequest {
+                identifier,
+                reply,
+            }))
+            .map_err(|_| SharedCodeAgentError::Canceled("user interface is gone".to_string()))?;
+        (self.notify)();
+
+        code.recv().await.map_err(|_| {
+            SharedCodeAgentError::Canceled("request dismissed by the user".to_string())
+        })
     }
 }
 
-impl IwdDbus {
-    pub(crate) fn new() -> Result<Self, String> {
-        let conn = Connection::system().map_err(|e| e.to_string())?;
-        Ok(Self { conn })
+/// Forwards signal level changes of every station it is registered with.
+struct SignalLevelAgent {
+    events: Sender<IwdEvent>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl SignalLevelAgent {
+    fn send(&self, event: IwdEvent) {
+        let _ = self.events.send(event);
+        (self.notify)();
     }
+}
 
-    fn managed_objects(&self) -> Result<ManagedObjects, String> {
-        let proxy = Proxy::new(&self.conn, IWD_SERVICE, "/", OBJECT_MANAGER_IFACE)
-            .map_err(|e| e.to_string())?;
-        proxy
-            .call("GetManagedObjects", &())
-            .map_err(|e| e.to_string())
+#[zbus::interface(name = "net.connman.iwd.SignalLevelAgent")]
+impl SignalLevelAgent {
+    fn release(&self, device: OwnedObjectPath) {
+        self.send(IwdEvent::SignalLevelAgentReleased(
+            device.as_str().to_string(),
+        ));
     }
 
-    pub(crate) fn list_devices(&self) -> Result<Vec<DeviceInfo>, String> {
-        let objects = self.managed_objects()?;
+    fn changed(&self, device: OwnedObjectPath, level: u8) {
+        self.send(IwdEvent::SignalLevelChanged(
+            device.as_str().to_string(),
+            level,
+        ));
+    }
+}
+
+/// One `GetManagedObjects` reply, from which all models of a refresh are
+/// built so that a refresh costs a single round trip.
+#[derive(Debug)]
+pub(crate) struct IwdObjects {
+    objects: ManagedObjects,
+}
+
+impl IwdObjects {
+    pub(crate) fn adapters(&self) -> Result<Vec<AdapterInfo>, IwdError> {
         let mut out = Vec::new();
 
-        for (path, interfaces) in objects {
-            if !interfaces.contains_key(DEVICE_IFACE) {
-                continue;
+        for (path, interfaces) in &self.objects {
+            if let Some(props) = interfaces.get(ADAPTER_IFACE) {
+                out.push(adapter_from_props(path.as_str(), props)?);
             }
+        }
 
-            let path_str = path.as_str().to_string();
-            let proxy = Proxy::new(&self.conn, IWD_SERVICE, path.as_str(), DEVICE_IFACE)
-                .map_err(|e| e.to_string())?;
-            let name: String = proxy
-                .get_property("Name")
-                .map_err(|e| format!("Failed to read device name at {path_str}: {e}"))?;
-            out.push(DeviceInfo {
-                name,
-                path: path_str,
-            });
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(out)
+    }
+
+    pub(crate) fn devices(&self) -> Result<Vec<DeviceInfo>, IwdError> {
+        let mut out = Vec::new();
+
+        for (path, interfaces) in &self.objects {
+            if let Some(props) = interfaces.get(DEVICE_IFACE) {
+                out.push(device_from_props(path.as_str(), props)?);
+            }
         }
 
         out.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(out)
     }
 
-    pub(crate) fn list_visible_networks(
-        &self,
-        selected_device_path: Option<&str>,
-    ) -> Result<Vec<VisibleNetwork>, String> {
-        let objects = self.managed_objects()?;
+    pub(crate) fn known_networks(&self) -> Result<Vec<KnownNetwork>, IwdError> {
         let mut out = Vec::new();
 
-        for (path, interfaces) in objects {
-            if !interfaces.contains_key(NETWORK_IFACE) {
-                continue;
+        for (path, interfaces) in &self.objects {
+            if let Some(props) = interfaces.get(KNOWN_NETWORK_IFACE) {
+                out.push(known_network_from_props(path.as_str(), props)?);
             }
+        }
 
-            let path_str = path.as_str().to_string();
-            let proxy = Proxy::new(&self.conn, IWD_SERVICE, path.as_str(), NETWORK_IFACE)
-                .map_err(|e| e.to_string())?;
-
-            let ssid: String = proxy
-                .get_property("Name")
-                .map_err(|e| format!("Failed to read network name at {}: {e}", path.as_str()))?;
-            let security: String = proxy
-                .get_property("Type")
-                .unwrap_or_else(|_| "-".to_string());
-            let connected: bool = proxy.get_property("Connected").unwrap_or(false);
-            let signal_dbm: i16 = proxy.get_property("Signal").unwrap_or(0);
-            let signal = if signal_dbm == 0 {
-                "-".to_string()
-            } else {
-                format!("{signal_dbm} dBm")
-            };
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(out)
+    }
 
-            let device_path: Option<String> = proxy
-                .get_property::<OwnedObjectPath>("Device")
-                .ok()
-                .map(|v| v.as_str().to_string());
+    pub(crate) fn stations(&self) -> Vec<StationInfo> {
+        self.objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let props = interfaces.get(STATION_IFACE)?;
+                Some(station_from_props(path.as_str(), props))
+            })
+            .collect()
+    }
+
+    pub(crate) fn access_points(&self) -> Vec<AccessPointInfo> {
+        self.objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let props = interfaces.get(ACCESS_POINT_IFACE)?;
+                Some(access_point_from_props(path.as_str(), props))
+            })
+            .collect()
+    }
+
+    pub(crate) fn ad_hocs(&self) -> Vec<AdHocInfo> {
+        self.objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let props = interfaces.get(AD_HOC_IFACE)?;
+                Some(ad_hoc_from_props(path.as_str(), props))
+            })
+            .collect()
+    }
 
-            if let Some(sel) = selected_device_path {
-                if let Some(dev) = device_path.as_deref() {
-                    if dev != sel {
+    pub(crate) fn dpps(&self) -> Vec<DppInfo> {
+        self.objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let props = interfaces.get(DEVICE_PROVISIONING_IFACE)?;
+                Some(dpp_from_props(path.as_str(), props))
+            })
+            .collect()
+    }
+
+    pub(crate) fn pkexes(&self) -> Vec<PkexInfo> {
+        self.objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let props = interfaces.get(SHARED_CODE_PROVISIONING_IFACE)?;
+                Some(pkex_from_props(path.as_str(), props))
+            })
+            .collect()
+    }
+}
+
+impl IwdDbus {
+    pub(crate) fn new() -> Result<Self, IwdError> {
+        let conn = Connection::system()?;
+        Ok(Self { conn })
+    }
+
+    fn managed_objects(&self) -> Result<ManagedObjects, IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, "/", OBJECT_MANAGER_IFACE)?;
+        proxy.call("GetManagedObjects", &()).map_err(IwdError::from)
+    }
+
+    /// Unique name and objects of the running iwd instance. A failed query
+    /// yields no objects, and no owner if iwd is not running.
+    fn current_objects(&self) -> (Option<String>, ManagedObjects) {
+        let owner = DBusProxy::new(&self.conn).ok().and_then(|bus| {
+            let service = BusName::try_from(IWD_SERVICE).ok()?;
+            bus.get_name_owner(service).ok()
+        });
+        match owner {
+            Some(owner) => (
+                Some(owner.to_string()),
+                self.managed_objects().unwrap_or_default(),
+            ),
+            None => (None, ManagedObjects::default()),
+        }
+    }
+
+    pub(crate) fn objects(&self) -> Result<IwdObjects, IwdError> {
+        Ok(IwdObjects {
+            objects: self.managed_objects()?,
+        })
+    }
+
+    /// Starts background threads that listen for iwd object and property
+    /// changes, and for iwd itself appearing on or vanishing from the bus, and
+    /// forwards them as [`IwdEvent`]s. `notify` is called after each batch so
+    /// the caller can wake its UI.
+    pub(crate) fn subscribe<F>(&self, events: Sender<IwdEvent>, notify: F) -> Result<(), IwdError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(IWD_SERVICE)?
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, &self.conn, None)?;
+
+        let bus = DBusProxy::new(&self.conn)?;
+        let owner_changes = bus.receive_name_owner_changed_with_args(&[(0, IWD_SERVICE)])?;
+        let cache = Arc::new(Mutex::new(ObjectCache::default()));
+        let notify = Arc::new(notify);
+
+        let seed_backend = self.clone();
+        let signal_cache = Arc::clone(&cache);
+        let signal_events = events.clone();
+        let signal_notify = Arc::clone(&notify);
+        thread::Builder::new()
+            .name("iwd-signals".to_string())
+            .spawn(move || {
+                // Seed the cache only after the match rules are in place so no
+                // change is lost; signals queue up in `messages` meanwhile.
+                let (owner, objects) = seed_backend.current_objects();
+                let running = owner.is_some();
+                match signal_cache.lock() {
+                    Ok(mut cache) => cache.seed(owner, objects),
+                    Err(_) => return,
+                }
+                if !running {
+                    if signal_events.send(IwdEvent::ServiceVanished).is_err() {
+                        return;
+                    }
+                    signal_notify();
+                }
+
+                for msg in messages {
+                    let Ok(msg) = msg else {
                         continue;
+                    };
+                    let batch = match signal_cache.lock() {
+                        Ok(mut cache) => cache.handle_signal(&msg),
+                        Err(_) => return,
+                    };
+                    if batch.is_empty() {
+                        continue;
+                    }
+                    for event in batch {
+                        if signal_events.send(event).is_err() {
+                            return;
+                        }
                     }
+                    signal_notify();
                 }
-            }
+            })
+            .map_err(|e| IwdError::Internal(format!("Failed to start signal listener: {e}")))?;
 
-            out.push(VisibleNetwork {
-                ssid,
-                security,
-                signal,
-                connected,
-                path: path_str,
-                device_path,
-            });
-        }
+        let backend = self.clone();
+        thread::Builder::new()
+            .name("iwd-owner".to_string())
+            .spawn(move || {
+                for signal in owner_changes {
+                    let Ok(args) = signal.args() else {
+                        continue;
+                    };
+                    let old_owner = args.old_owner().as_ref().map(|o| o.to_string());
+                    let new_owner = args.new_owner().as_ref().map(|o| o.to_string());
+                    let objects = match new_owner {
+                        Some(_) => backend.managed_objects().unwrap_or_default(),
+                        None => ManagedObjects::default(),
+                    };
+                    // The signal thread may already have switched to the new
+                    // instance and cached its first objects.
+                    match cache.lock() {
+                        Ok(mut cache) if new_owner.is_some() => {
+                            if cache.owner != new_owner {
+                                cache.reset(new_owner.clone());
+                            }
+                            cache.merge(objects);
+                        }
+                        Ok(mut cache) if cache.owner == old_owner => cache.reset(None),
+                        Ok(_) => {}
+                        Err(_) => return,
+                    }
 
-        out.sort_by(|a, b| a.ssid.cmp(&b.ssid));
-        Ok(out)
+                    let event = if new_owner.is_some() {
+                        IwdEvent::ServiceAppeared
+                    } else {
+                        IwdEvent::ServiceVanished
+                    };
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                    notify();
+                }
+            })
+            .map_err(|e| IwdError::Internal(format!("Failed to start iwd service watcher: {e}")))?;
+
+        Ok(())
+    }
+
+    pub(crate) fn set_adapter_powered(
+        &self,
+        adapter_path: &str,
+        powered: bool,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, adapter_path, ADAPTER_IFACE)?;
+        proxy
+            .set_property("Powered", powered)
+            .map_err(IwdError::from)
     }
 
-    pub(crate) fn list_known_networks(&self) -> Result<Vec<KnownNetwork>, String> {
-        let objects = self.managed_objects()?;
+    pub(crate) fn set_device_powered(
+        &self,
+        device_path: &str,
+        powered: bool,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, DEVICE_IFACE)?;
+        proxy
+            .set_property("Powered", powered)
+            .map_err(IwdError::from)
+    }
+
+    pub(crate) fn set_device_mode(
+        &self,
+        device_path: &str,
+        mode: &DeviceMode,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, DEVICE_IFACE)?;
+        proxy
+            .set_property("Mode", mode.as_iwd())
+            .map_err(IwdError::from)
+    }
+
+    pub(crate) fn list_visible_networks(&self, objects: &IwdObjects) -> Vec<VisibleNetwork> {
+        let objects = &objects.objects;
         let mut out = Vec::new();
 
-        for (path, interfaces) in objects {
-            if !interfaces.contains_key(KNOWN_NETWORK_IFACE) {
+        for (device_path, interfaces) in objects {
+            if !interfaces.contains_key(STATION_IFACE) {
                 continue;
             }
 
-            let proxy = Proxy::new(&self.conn, IWD_SERVICE, path.as_str(), KNOWN_NETWORK_IFACE)
-                .map_err(|e| e.to_string())?;
-
-            let name: String = proxy
-                .get_property("Name")
-                .map_err(|e| format!("Failed to read known network name: {e}"))?;
-            let network_type: String = proxy
-                .get_property("Type")
-                .unwrap_or_else(|_| "-".to_string());
-            let autoconnect: Option<bool> = proxy.get_property("AutoConnect").ok();
-            let hidden: Option<bool> = proxy.get_property("Hidden").ok();
-
-            out.push(KnownNetwork {
-                name,
-                network_type,
-                autoconnect,
-                hidden,
-                path: path.as_str().to_string(),
-            });
+            // The station may go away or switch mode after `GetManagedObjects`;
+            // skip it rather than failing the whole refresh.
+            let Ok(ordered) = self.ordered_networks(device_path.as_str()) else {
+                continue;
+            };
+            for (rank, (path, signal)) in ordered.into_iter().enumerate() {
+                let Some(props) = objects
+                    .get(&path)
+                    .and_then(|interfaces| interfaces.get(NETWORK_IFACE))
+                else {
+                    continue;
+                };
+                let Ok(mut network) = visible_network_from_props(path.as_str(), props) else {
+                    continue;
+                };
+
+                network.signal = Some(signal);
+                network.rank = Some(rank);
+                out.push(network);
+            }
         }
 
-        out.sort_by(|a, b| a.name.cmp(&b.name));
-        Ok(out)
+        out
+    }
+
+    /// Networks seen by a station, best first, with signal in 100 * dBm.
+    fn ordered_networks(&self, device_path: &str) -> Result<Vec<(OwnedObjectPath, i16)>, IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        proxy
+            .call("GetOrderedNetworks", &())
+            .map_err(IwdError::from)
+    }
+
+    pub(crate) fn start_access_point(
+        &self,
+        device_path: &str,
+        ssid: &str,
+        psk: &str,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, ACCESS_POINT_IFACE)?;
+        let _: () = proxy.call("Start", &(ssid, psk))?;
+        Ok(())
+    }
+
+    /// Starts the access point from `/var/lib/iwd/ap/<ssid>.ap`.
+    pub(crate) fn start_access_point_profile(
+        &self,
+        device_path: &str,
+        ssid: &str,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, ACCESS_POINT_IFACE)?;
+        let _: () = proxy.call("StartProfile", &(ssid,))?;
+        Ok(())
+    }
+
+    pub(crate) fn stop_access_point(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, ACCESS_POINT_IFACE)?;
+        let _: () = proxy.call("Stop", &())?;
+        Ok(())
+    }
+
+    /// Starts an IBSS; without a passphrase the network is open.
+    pub(crate) fn start_ad_hoc(
+        &self,
+        device_path: &str,
+        ssid: &str,
+        psk: Option<&str>,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, AD_HOC_IFACE)?;
+        let _: () = match psk {
+            Some(psk) => proxy.call("Start", &(ssid, psk))?,
+            None => proxy.call("StartOpen", &(ssid,))?,
+        };
+        Ok(())
+    }
+
+    pub(crate) fn stop_ad_hoc(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, AD_HOC_IFACE)?;
+        let _: () = proxy.call("Stop", &())?;
+        Ok(())
+    }
+
+    /// Runs WPS push-button mode. Blocks until iwd connects, the walk time
+    /// expires or `cancel_wps` is called.
+    pub(crate) fn wps_push_button(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SIMPLE_CONFIGURATION_IFACE,
+        )?;
+        let _: () = proxy.call("PushButton", &())?;
+        Ok(())
+    }
+
+    pub(crate) fn wps_generate_pin(&self, device_path: &str) -> Result<String, IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SIMPLE_CONFIGURATION_IFACE,
+        )?;
+        Ok(proxy.call("GeneratePin", &())?)
+    }
+
+    /// Runs WPS PIN mode; blocks like `wps_push_button`.
+    pub(crate) fn wps_start_pin(&self, device_path: &str, pin: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SIMPLE_CONFIGURATION_IFACE,
+        )?;
+        let _: () = proxy.call("StartPin", &(pin,))?;
+        Ok(())
+    }
+
+    pub(crate) fn cancel_wps(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SIMPLE_CONFIGURATION_IFACE,
+        )?;
+        let _: () = proxy.call("Cancel", &())?;
+        Ok(())
+    }
+
+    /// Starts DPP as an enrollee and returns the URI for a configurator to scan.
+    pub(crate) fn dpp_start_enrollee(&self, device_path: &str) -> Result<String, IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            DEVICE_PROVISIONING_IFACE,
+        )?;
+        Ok(proxy.call("StartEnrollee", &())?)
+    }
+
+    /// Starts DPP as a configurator for the connected network and returns the
+    /// URI for an enrollee to scan.
+    pub(crate) fn dpp_start_configurator(&self, device_path: &str) -> Result<String, IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            DEVICE_PROVISIONING_IFACE,
+        )?;
+        Ok(proxy.call("StartConfigurator", &())?)
+    }
+
+    /// Provisions the enrollee behind `uri` with the connected network.
+    pub(crate) fn dpp_configure_enrollee(
+        &self,
+        device_path: &str,
+        uri: &str,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            DEVICE_PROVISIONING_IFACE,
+        )?;
+        let _: () = proxy.call("ConfigureEnrollee", &(uri,))?;
+        Ok(())
+    }
+
+    pub(crate) fn dpp_stop(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            DEVICE_PROVISIONING_IFACE,
+        )?;
+        let _: () = proxy.call("Stop", &())?;
+        Ok(())
+    }
+
+    pub(crate) fn pkex_start_enrollee(
+        &self,
+        device_path: &str,
+        code: &str,
+        identifier: Option<&str>,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SHARED_CODE_PROVISIONING_IFACE,
+        )?;
+        let _: () = proxy.call("StartEnrollee", &(pkex_args(code, identifier),))?;
+        Ok(())
+    }
+
+    /// Provisions a single enrollee that knows `code` with the connected network.
+    pub(crate) fn pkex_configure_enrollee(
+        &self,
+        device_path: &str,
+        code: &str,
+        identifier: Option<&str>,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SHARED_CODE_PROVISIONING_IFACE,
+        )?;
+        let _: () = proxy.call("ConfigureEnrollee", &(pkex_args(code, identifier),))?;
+        Ok(())
+    }
+
+    /// Starts a configurator that asks the shared-code agent for each
+    /// enrollee's code; see `serve_shared_code_agent`.
+    pub(crate) fn pkex_start_configurator(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SHARED_CODE_PROVISIONING_IFACE,
+        )?;
+        let path =
+            ObjectPath::try_from(SHARED_CODE_AGENT_OBJECT_PATH).map_err(zbus::Error::from)?;
+        let _: () = proxy.call("StartConfigurator", &(path,))?;
+        Ok(())
+    }
+
+    pub(crate) fn pkex_stop(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            SHARED_CODE_PROVISIONING_IFACE,
+        )?;
+        let _: () = proxy.call("Stop", &())?;
+        Ok(())
+    }
+
+    pub(crate) fn scan(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        let _: () = proxy.call("Scan", &())?;
+        Ok(())
     }
 
-    pub(crate) fn scan(&self, device_path: &str) -> Result<(), String> {
-        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)
-            .map_err(|e| e.to_string())?;
-        let _: () = proxy.call("Scan", &()).map_err(|e| e.to_string())?;
+    /// Exports the agent object on this connection. It stays in place for the
+    /// lifetime of the connection; [`IwdDbus::register_agent`] announces it to
+    /// iwd and must be repeated whenever iwd restarts.
+    pub(crate) fn serve_agent<F>(&self, events: Sender<IwdEvent>, notify: F) -> Result<(), IwdError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let agent = IwdAgent {
+            events,
+            notify: Arc::new(notify),
+        };
+        self.conn.object_server().at(AGENT_OBJECT_PATH, agent)?;
         Ok(())
     }
 
-    pub(crate) fn connect_network(
+    pub(crate) fn serve_shared_code_agent<F>(
         &self,
-        network_path: &str,
-        passphrase: Option<&str>,
-    ) -> Result<(), String> {
-        let _agent = if let Some(passphrase) = passphrase {
-            Some(RegisteredAgent::new(&self.conn, passphrase)?)
-        } else {
-            None
+        events: Sender<IwdEvent>,
+        notify: F,
+    ) -> Result<(), IwdError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let agent = SharedCodeAgent {
+            events,
+            notify: Arc::new(notify),
         };
-        let proxy = Proxy::new(&self.conn, IWD_SERVICE, network_path, NETWORK_IFACE)
-            .map_err(|e| e.to_string())?;
-        let _: () = proxy.call("Connect", &()).map_err(|e| e.to_string())?;
+        self.conn
+            .object_server()
+            .at(SHARED_CODE_AGENT_OBJECT_PATH, agent)?;
         Ok(())
     }
 
-    pub(crate) fn forget_known_network(&self, known_path: &str) -> Result<(), String> {
-        let proxy = Proxy::new(&self.conn, IWD_SERVICE, known_path, KNOWN_NETWORK_IFACE)
-            .map_err(|e| e.to_string())?;
-        let _: () = proxy.call("Forget", &()).map_err(|e| e.to_string())?;
+    pub(crate) fn serve_signal_level_agent<F>(
+        &self,
+        events: Sender<IwdEvent>,
+        notify: F,
+    ) -> Result<(), IwdError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let agent = SignalLevelAgent {
+            events,
+            notify: Arc::new(notify),
+        };
+        self.conn
+            .object_server()
+            .at(SIGNAL_LEVEL_AGENT_OBJECT_PATH, agent)?;
+        Ok(())
+    }
+
+    /// Asks iwd to report crossings of [`SIGNAL_LEVELS`] on this station to the
+    /// signal level agent. iwd answers with the current level right away.
+    pub(crate) fn register_signal_level_agent(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        let path =
+            ObjectPath::try_from(SIGNAL_LEVEL_AGENT_OBJECT_PATH).map_err(zbus::Error::from)?;
+        let _: () = proxy.call("RegisterSignalLevelAgent", &(path, &SIGNAL_LEVELS[..]))?;
+        Ok(())
+    }
+
+    pub(crate) fn register_agent(&self) -> Result<(), IwdError> {
+        let manager = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            "/net/connman/iwd",
+            AGENT_MANAGER_IFACE,
+        )?;
+        let path = ObjectPath::try_from(AGENT_OBJECT_PATH).map_err(zbus::Error::from)?;
+        let _: () = manager.call("RegisterAgent", &(path))?;
+        Ok(())
+    }
+
+    pub(crate) fn connect_network(&self, network_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, network_path, NETWORK_IFACE)?;
+        let _: () = proxy.call("Connect", &())?;
+        Ok(())
+    }
+
+    pub(crate) fn connect_hidden_network(
+        &self,
+        device_path: &str,
+        ssid: &str,
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        let _: () = proxy.call("ConnectHiddenNetwork", &(ssid))?;
+        Ok(())
+    }
+
+    pub(crate) fn hidden_access_points(
+        &self,
+        device_path: &str,
+    ) -> Result<Vec<HiddenAccessPoint>, IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        let entries: Vec<(String, i16, String)> = proxy.call("GetHiddenAccessPoints", &())?;
+        Ok(entries
+            .into_iter()
+            .map(|(address, signal, security)| HiddenAccessPoint {
+                address,
+                signal,
+                security,
+                device_path: device_path.to_string(),
+            })
+            .collect())
+    }
+
+    pub(crate) fn diagnostics(&self, device_path: &str) -> Result<StationDiagnostics, IwdError> {
+        let proxy = Proxy::new(
+            &self.conn,
+            IWD_SERVICE,
+            device_path,
+            STATION_DIAGNOSTIC_IFACE,
+        )?;
+        let values: PropMap = proxy.call("GetDiagnostics", &())?;
+        Ok(StationDiagnostics {
+            connected_bss: prop(&values, "ConnectedBss"),
+            frequency: prop(&values, "Frequency"),
+            channel: prop(&values, "Channel"),
+            security: prop(&values, "Security"),
+            rssi: prop(&values, "RSSI"),
+            average_rssi: prop(&values, "AverageRSSI"),
+            rx_mode: prop(&values, "RxMode"),
+            rx_bitrate: prop(&values, "RxBitrate"),
+            rx_mcs: prop(&values, "RxMCS"),
+            tx_mode: prop(&values, "TxMode"),
+            tx_bitrate: prop(&values, "TxBitrate"),
+            tx_mcs: prop(&values, "TxMCS"),
+            inactive_time: prop(&values, "InactiveTime"),
+        })
+    }
+
+    pub(crate) fn disconnect(&self, device_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, device_path, STATION_IFACE)?;
+        let _: () = proxy.call("Disconnect", &())?;
+        Ok(())
+    }
+
+    pub(crate) fn forget_known_network(&self, known_path: &str) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, known_path, KNOWN_NETWORK_IFACE)?;
+        let _: () = proxy.call("Forget", &())?;
         Ok(())
     }
 
@@ -299,11 +1463,274 @@ impl IwdDbus {
         &self,
         known_path: &str,
         enabled: bool,
-    ) -> Result<(), String> {
-        let proxy = Proxy::new(&self.conn, IWD_SERVICE, known_path, KNOWN_NETWORK_IFACE)
-            .map_err(|e| e.to_string())?;
+    ) -> Result<(), IwdError> {
+        let proxy = Proxy::new(&self.conn, IWD_SERVICE, known_path, KNOWN_NETWORK_IFACE)?;
         proxy
             .set_property("AutoConnect", enabled)
-            .map_err(|e| e.to_string())
+            .map_err(IwdError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE_PATH: &str = "/net/connman/iwd/0/3";
+
+    fn value<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+        value.into().try_to_owned().unwrap()
+    }
+
+    fn device_props(name: &str, powered: bool) -> PropMap {
+        HashMap::from([
+            ("Name".to_string(), value(name)),
+            ("Powered".to_string(), value(powered)),
+        ])
+    }
+
+    fn device_objects(name: &str, powered: bool) -> ManagedObjects {
+        let path = OwnedObjectPath::try_from(DEVICE_PATH).unwrap();
+        HashMap::from([(
+            path,
+            HashMap::from([(DEVICE_IFACE.to_string(), device_props(name, powered))]),
+        )])
+    }
+
+    fn powered_changed(sender: &str, powered: bool) -> Message {
+        let changed = HashMap::from([("Powered".to_string(), value(powered))]);
+        Message::signal(DEVICE_PATH, PROPERTIES_IFACE, "PropertiesChanged")
+            .unwrap()
+            .sender(sender)
+            .unwrap()
+            .build(&(DEVICE_IFACE, changed, Vec::<String>::new()))
+            .unwrap()
+    }
+
+    fn interfaces_removed(sender: &str) -> Message {
+        let path = OwnedObjectPath::try_from(DEVICE_PATH).unwrap();
+        Message::signal("/", OBJECT_MANAGER_IFACE, "InterfacesRemoved")
+            .unwrap()
+            .sender(sender)
+            .unwrap()
+            .build(&(path, vec![DEVICE_IFACE.to_string()]))
+            .unwrap()
+    }
+
+    fn name_owner_changed() -> Message {
+        Message::signal(
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameOwnerChanged",
+        )
+        .unwrap()
+        .sender("org.freedesktop.DBus")
+        .unwrap()
+        .build(&(IWD_SERVICE, "", ":1.9"))
+        .unwrap()
+    }
+
+    fn seeded_cache() -> ObjectCache {
+        let mut cache = ObjectCache::default();
+        cache.seed(Some(":1.7".to_string()), device_objects("wlan0", false));
+        cache
+    }
+
+    fn device_powered(cache: &ObjectCache) -> Option<bool> {
+        let path = OwnedObjectPath::try_from(DEVICE_PATH).unwrap();
+        let props = cache.objects.get(&path)?.get(DEVICE_IFACE)?;
+        prop(props, "Powered")
+    }
+
+    fn single_device_update(events: Vec<IwdEvent>) -> DeviceInfo {
+        match events.as_slice() {
+            [IwdEvent::DeviceUpdated(device)] => device.clone(),
+            other => panic!("expected one DeviceUpdated, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn properties_changed_updates_cached_object() {
+        let mut cache = seeded_cache();
+
+        let device = single_device_update(cache.handle_signal(&powered_changed(":1.7", true)));
+        assert_eq!(device.name, "wlan0");
+        assert!(device.powered);
+    }
+
+    #[test]
+    fn bus_daemon_signals_keep_the_cache() {
+        let mut cache = seeded_cache();
+
+        assert!(cache.handle_signal(&name_owner_changed()).is_empty());
+        assert_eq!(cache.owner.as_deref(), Some(":1.7"));
+        assert_eq!(device_powered(&cache), Some(false));
+    }
+
+    #[test]
+    fn signal_from_new_instance_resets_the_cache() {
+        let mut cache = seeded_cache();
+
+        assert!(
+            cache
+                .handle_signal(&powered_changed(":1.9", true))
+                .is_empty()
+        );
+        assert_eq!(cache.owner.as_deref(), Some(":1.9"));
+        assert!(cache.objects.is_empty());
+    }
+
+    #[test]
+    fn restart_snapshot_survives_later_bus_daemon_signals() {
+        let mut cache = seeded_cache();
+
+        // The owner watcher handles the restart before any iwd signal arrives.
+        cache.reset(Some(":1.9".to_string()));
+        cache.merge(device_objects("wlan0", false));
+        assert!(cache.handle_signal(&name_owner_changed()).is_empty());
+
+        let device = single_device_update(cache.handle_signal(&powered_changed(":1.9", true)));
+        assert!(device.powered);
+    }
+
+    #[test]
+    fn seed_is_dropped_after_a_restart() {
+        let mut cache = ObjectCache::default();
+        cache.reset(Some(":1.9".to_string()));
+
+        cache.seed(Some(":1.7".to_string()), device_objects("wlan0", false));
+        assert_eq!(cache.owner.as_deref(), Some(":1.9"));
+        assert!(cache.objects.is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_newer_signal_values() {
+        let mut cache = seeded_cache();
+        cache.handle_signal(&powered_changed(":1.7", true));
+
+        cache.merge(device_objects("wlan0", false));
+        assert_eq!(device_powered(&cache), Some(true));
+    }
+
+    #[test]
+    fn merge_does_not_restore_removed_interfaces() {
+        let mut cache = seeded_cache();
+        let events = cache.handle_signal(&interfaces_removed(":1.7"));
+        assert!(
+            matches!(events.as_slice(), [IwdEvent::DeviceRemoved(path)] if path == DEVICE_PATH)
+        );
+
+        cache.merge(device_objects("wlan0", false));
+        assert_eq!(device_powered(&cache), None);
+    }
+
+    #[test]
+    fn reset_forgets_removed_interfaces() {
+        let mut cache = seeded_cache();
+        cache.handle_signal(&interfaces_removed(":1.7"));
+
+        cache.reset(Some(":1.9".to_string()));
+        cache.merge(device_objects("wlan0", true));
+        assert_eq!(device_powered(&cache), Some(true));
+    }
+
+    fn iwd_error(name: &str) -> IwdError {
+        IwdError::from_error_name(name, "message".to_string())
+    }
+
+    #[test]
+    fn iwd_error_names_map_to_variants() {
+        assert_eq!(iwd_error("net.connman.iwd.Error.Busy"), IwdError::Busy);
+        assert_eq!(
+            iwd_error("net.connman.iwd.Error.InvalidFormat"),
+            IwdError::InvalidFormat("message".to_string())
+        );
+        assert_eq!(
+            iwd_error("net.connman.iwd.Error.NotImplemented"),
+            IwdError::NotSupported
+        );
+        assert_eq!(
+            iwd_error("org.freedesktop.DBus.Error.AccessDenied"),
+            IwdError::PermissionDenied
+        );
+        assert_eq!(
+            iwd_error("org.freedesktop.DBus.Error.ServiceUnknown"),
+            IwdError::ServiceUnavailable
+        );
+        assert_eq!(
+            iwd_error("org.freedesktop.DBus.Error.NoReply"),
+            IwdError::Timeout
+        );
+        assert_eq!(
+            iwd_error("net.connman.iwd.Error.NotHidden"),
+            IwdError::Other {
+                name: "net.connman.iwd.Error.NotHidden".to_string(),
+                message: "message".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        let retryable = [
+            "net.connman.iwd.Error.Busy",
+            "net.connman.iwd.Error.InProgress",
+            "net.connman.iwd.Error.Aborted",
+            "net.connman.iwd.Error.NotAvailable",
+            "org.freedesktop.DBus.Error.NoReply",
+        ];
+        let permanent = [
+            "net.connman.iwd.Error.InvalidFormat",
+            "net.connman.iwd.Error.NotImplemented",
+            "org.freedesktop.DBus.Error.AccessDenied",
+            "org.freedesktop.DBus.Error.ServiceUnknown",
+            "net.connman.iwd.Error.NotHidden",
+        ];
+        for name in retryable {
+            assert!(iwd_error(name).is_retryable(), "{name}");
+        }
+        for name in permanent {
+            assert!(!iwd_error(name).is_retryable(), "{name}");
+        }
+    }
+
+    #[test]
+    fn zbus_method_errors_use_the_error_name() {
+        let call = Message::method_call("/", "Connect")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        let name = zbus::names::OwnedErrorName::try_from("net.connman.iwd.Error.Failed").unwrap();
+        let err = zbus::Error::MethodError(name, Some("wrong key".to_string()), call);
+        assert_eq!(
+            IwdError::from(err),
+            IwdError::Failed("wrong key".to_string())
+        );
+    }
+
+    #[test]
+    fn fdo_errors_map_to_variants() {
+        let cases = [
+            (
+                zbus::fdo::Error::AccessDenied(String::new()),
+                IwdError::PermissionDenied,
+            ),
+            (
+                zbus::fdo::Error::ServiceUnknown(String::new()),
+                IwdError::ServiceUnavailable,
+            ),
+            (
+                zbus::fdo::Error::NameHasNoOwner(String::new()),
+                IwdError::ServiceUnavailable,
+            ),
+            (zbus::fdo::Error::NoReply(String::new()), IwdError::Timeout),
+            (zbus::fdo::Error::Timeout(String::new()), IwdError::Timeout),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(IwdError::from(err), expected);
+        }
+        assert!(matches!(
+            IwdError::from(zbus::fdo::Error::Failed("boom".to_string())),
+            IwdError::Bus(_)
+        ));
     }
 }
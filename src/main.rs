@@ -1,6 +1,8 @@
 mod app;
 mod dbus;
 mod models;
+mod share;
+mod worker;
 
 use app::IwdGuiApp;
 
@@ -9,7 +11,7 @@ fn main() {
     let run = eframe::run_native(
         "iwd-gui",
         options,
-        Box::new(|_cc| Ok(Box::new(IwdGuiApp::default()))),
+        Box::new(|cc| Ok(Box::new(IwdGuiApp::new(&cc.egui_ctx)))),
     );
 
     if let Err(err) = run {
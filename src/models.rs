@@ -1,19 +1,286 @@
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AdapterInfo {
+    pub(crate) name: String,
+    pub(crate) model: Option<String>,
+    pub(crate) vendor: Option<String>,
+    pub(crate) powered: bool,
+    pub(crate) supported_modes: Vec<String>,
+    pub(crate) path: String,
+}
+
+impl AdapterInfo {
+    pub(crate) fn supports(&self, mode: &DeviceMode) -> bool {
+        self.supported_modes.iter().any(|m| m == mode.as_iwd())
+    }
+}
+
+/// `Device.Mode` as reported by iwd.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) enum DeviceMode {
+    #[default]
+    Station,
+    AccessPoint,
+    AdHoc,
+    Other(String),
+}
+
+impl DeviceMode {
+    /// The modes that can be written to `Device.Mode`.
+    pub(crate) const SELECTABLE: [DeviceMode; 3] = [
+        DeviceMode::Station,
+        DeviceMode::AccessPoint,
+        DeviceMode::AdHoc,
+    ];
+
+    pub(crate) fn from_iwd(mode: &str) -> Self {
+        match mode {
+            "station" => DeviceMode::Station,
+            "ap" => DeviceMode::AccessPoint,
+            "ad-hoc" => DeviceMode::AdHoc,
+            other => DeviceMode::Other(other.to_string()),
+        }
+    }
+
+    pub(crate) fn as_iwd(&self) -> &str {
+        match self {
+            DeviceMode::Station => "station",
+            DeviceMode::AccessPoint => "ap",
+            DeviceMode::AdHoc => "ad-hoc",
+            DeviceMode::Other(other) => other,
+        }
+    }
+
+    pub(crate) fn label(&self) -> &str {
+        match self {
+            DeviceMode::Station => "Station",
+            DeviceMode::AccessPoint => "Access point",
+            DeviceMode::AdHoc => "Ad-hoc",
+            DeviceMode::Other(other) => other,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct DeviceInfo {
     pub(crate) name: String,
+    pub(crate) address: String,
+    pub(crate) mode: DeviceMode,
+    pub(crate) powered: bool,
+    pub(crate) adapter_path: Option<String>,
     pub(crate) path: String,
 }
 
+impl DeviceInfo {
+    pub(crate) fn label(&self) -> String {
+        if self.powered {
+            self.name.clone()
+        } else {
+            format!("{} (off)", self.name)
+        }
+    }
+}
+
+/// `Station.State` as reported by iwd.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) enum StationState {
+    Connected,
+    Connecting,
+    Roaming,
+    Disconnecting,
+    #[default]
+    Disconnected,
+    Other(String),
+}
+
+impl StationState {
+    pub(crate) fn from_iwd(state: &str) -> Self {
+        match state {
+            "connected" => StationState::Connected,
+            "connecting" => StationState::Connecting,
+            "roaming" => StationState::Roaming,
+            "disconnecting" => StationState::Disconnecting,
+            "disconnected" => StationState::Disconnected,
+            other => StationState::Other(other.to_string()),
+        }
+    }
+
+    pub(crate) fn label(&self) -> &str {
+        match self {
+            StationState::Connected => "connected",
+            StationState::Connecting => "connecting",
+            StationState::Roaming => "roaming",
+            StationState::Disconnecting => "disconnecting",
+            StationState::Disconnected => "disconnected",
+            StationState::Other(other) => other,
+        }
+    }
+}
+
+/// The `net.connman.iwd.Station` interface of a device in station mode.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StationInfo {
+    pub(crate) path: String,
+    pub(crate) state: StationState,
+    pub(crate) connected_network: Option<String>,
+    pub(crate) scanning: bool,
+}
+
+/// The `net.connman.iwd.AccessPoint` interface of a device in `ap` mode.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AccessPointInfo {
+    pub(crate) path: String,
+    pub(crate) started: bool,
+    pub(crate) name: Option<String>,
+    /// Operating frequency in MHz, only set while started.
+    pub(crate) frequency: Option<u32>,
+    pub(crate) pairwise_ciphers: Vec<String>,
+}
+
+/// The `net.connman.iwd.AdHoc` interface of a device in `ad-hoc` mode.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AdHocInfo {
+    pub(crate) path: String,
+    pub(crate) started: bool,
+    /// MAC addresses of the peers in the IBSS.
+    pub(crate) connected_peers: Vec<String>,
+}
+
+/// The `net.connman.iwd.DeviceProvisioning` (DPP) interface of a station.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DppInfo {
+    pub(crate) path: String,
+    pub(crate) started: bool,
+    /// `enrollee` or `configurator` while started.
+    pub(crate) role: Option<String>,
+    /// The `DPP:` URI to show as a QR code while started.
+    pub(crate) uri: Option<String>,
+}
+
+/// The `net.connman.iwd.SharedCodeDeviceProvisioning` (PKEX) interface of a
+/// station.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PkexInfo {
+    pub(crate) path: String,
+    pub(crate) started: bool,
+    /// `enrollee` or `configurator` while started.
+    pub(crate) role: Option<String>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct VisibleNetwork {
     pub(crate) ssid: String,
     pub(crate) security: String,
-    pub(crate) signal: String,
+    /// Signal strength in 100 * dBm from `Station.GetOrderedNetworks`, if known.
+    pub(crate) signal: Option<i16>,
+    /// Position in iwd's ordered network list for the station (0 is best).
+    pub(crate) rank: Option<usize>,
     pub(crate) connected: bool,
     pub(crate) path: String,
     pub(crate) device_path: Option<String>,
 }
 
+/// Converts iwd's 100 * dBm signal value to dBm.
+pub(crate) fn signal_dbm(signal: i16) -> f32 {
+    f32::from(signal) / 100.0
+}
+
+/// Maps an iwd signal value (100 * dBm) to a 0-100 quality percentage,
+/// treating -100 dBm as unusable and -50 dBm or better as excellent.
+pub(crate) fn signal_quality(signal: i16) -> u8 {
+    let quality = 2.0 * (signal_dbm(signal) + 100.0);
+    quality.clamp(0.0, 100.0).round() as u8
+}
+
+/// Thresholds in dBm registered with `Station.RegisterSignalLevelAgent`, in
+/// the descending order iwd requires. They fall on 10% steps of
+/// [`signal_quality`].
+pub(crate) const SIGNAL_LEVELS: [i16; 10] = [-50, -55, -60, -65, -70, -75, -80, -85, -90, -95];
+
+/// Signal strength as shown in the UI: an exact reading from a scan, or the
+/// range a `SignalLevelAgent.Changed` level stands for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Signal {
+    /// In iwd's 100 * dBm unit.
+    Exact(i16),
+    /// Index into the ranges between [`SIGNAL_LEVELS`]; 0 is the strongest and
+    /// `SIGNAL_LEVELS.len()` the range below the last threshold.
+    Level(u8),
+}
+
+impl Signal {
+    /// The reading, or the lower bound of the range, in 100 * dBm. Used for
+    /// the bars and for sorting.
+    pub(crate) fn floor(self) -> i16 {
+        match self {
+            Signal::Exact(signal) => signal,
+            Signal::Level(level) => {
+                let dbm = SIGNAL_LEVELS
+                    .get(usize::from(level))
+                    .copied()
+                    .unwrap_or(-100);
+                dbm * 100
+            }
+        }
+    }
+
+    /// e.g. "-62.0 dBm" for a reading, "-60 to -65 dBm" for a range.
+    pub(crate) fn describe(self) -> String {
+        match self {
+            Signal::Exact(signal) => format!("{:.1} dBm", signal_dbm(signal)),
+            Signal::Level(level) => {
+                let level = usize::from(level);
+                match (level.checked_sub(1), SIGNAL_LEVELS.get(level)) {
+                    (None, Some(lower)) => format!("{lower} dBm or stronger"),
+                    (Some(upper), Some(lower)) => {
+                        format!("{} to {lower} dBm", SIGNAL_LEVELS[upper])
+                    }
+                    _ => format!("below {} dBm", SIGNAL_LEVELS[SIGNAL_LEVELS.len() - 1]),
+                }
+            }
+        }
+    }
+}
+
+/// The typed result of `StationDiagnostic.GetDiagnostics`. Every field is
+/// optional because drivers report different subsets.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StationDiagnostics {
+    pub(crate) connected_bss: Option<String>,
+    /// In MHz.
+    pub(crate) frequency: Option<u32>,
+    pub(crate) channel: Option<u16>,
+    pub(crate) security: Option<String>,
+    /// In dBm.
+    pub(crate) rssi: Option<i16>,
+    pub(crate) average_rssi: Option<i16>,
+    pub(crate) rx_mode: Option<String>,
+    /// In 100 kbit/s.
+    pub(crate) rx_bitrate: Option<u32>,
+    pub(crate) rx_mcs: Option<u8>,
+    pub(crate) tx_mode: Option<String>,
+    /// In 100 kbit/s.
+    pub(crate) tx_bitrate: Option<u32>,
+    pub(crate) tx_mcs: Option<u8>,
+    /// Milliseconds since the last activity on the link.
+    pub(crate) inactive_time: Option<u32>,
+}
+
+/// Converts iwd's 100 kbit/s bitrate unit to Mbit/s.
+pub(crate) fn bitrate_mbps(bitrate: u32) -> f32 {
+    bitrate as f32 / 10.0
+}
+
+/// A BSS that does not broadcast its SSID, as reported by
+/// `Station.GetHiddenAccessPoints`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HiddenAccessPoint {
+    pub(crate) address: String,
+    /// Signal strength in 100 * dBm, as reported by iwd.
+    pub(crate) signal: i16,
+    pub(crate) security: String,
+    pub(crate) device_path: String,
+}
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct KnownNetwork {
     pub(crate) name: String,
@@ -23,9 +290,82 @@ pub(crate) struct KnownNetwork {
     pub(crate) path: String,
 }
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum NetworkSort {
+    #[default]
+    Rank,
+    Signal,
+    Ssid,
+    Security,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum WpsMode {
+    #[default]
+    PushButton,
+    Pin,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum PkexRole {
+    #[default]
+    Enrollee,
+    Configurator,
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub(crate) enum ActiveTab {
     #[default]
     Networks,
     Saved,
+    Hotspot,
+    EasyConnect,
+    Diagnostics,
+    Devices,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_dbm_divides_by_hundred() {
+        assert_eq!(signal_dbm(-6250), -62.5);
+        assert_eq!(signal_dbm(0), 0.0);
+    }
+
+    #[test]
+    fn signal_quality_boundaries() {
+        assert_eq!(signal_quality(-10000), 0);
+        assert_eq!(signal_quality(-7500), 50);
+        assert_eq!(signal_quality(-5000), 100);
+    }
+
+    #[test]
+    fn signal_quality_clamps() {
+        assert_eq!(signal_quality(-3000), 100);
+        assert_eq!(signal_quality(-12000), 0);
+    }
+
+    #[test]
+    fn level_floor_is_the_lower_threshold() {
+        assert_eq!(Signal::Level(0).floor(), -5000);
+        assert_eq!(Signal::Level(4).floor(), -7000);
+        assert_eq!(Signal::Level(9).floor(), -9500);
+        assert_eq!(Signal::Level(SIGNAL_LEVELS.len() as u8).floor(), -10000);
+        assert_eq!(Signal::Level(200).floor(), -10000);
+        assert_eq!(Signal::Exact(-6250).floor(), -6250);
+    }
+
+    #[test]
+    fn describe_levels_as_ranges() {
+        assert_eq!(Signal::Level(0).describe(), "-50 dBm or stronger");
+        assert_eq!(Signal::Level(3).describe(), "-60 to -65 dBm");
+        assert_eq!(
+            Signal::Level(SIGNAL_LEVELS.len() as u8).describe(),
+            "below -95 dBm"
+        );
+        assert_eq!(Signal::Level(200).describe(), "below -95 dBm");
+        assert_eq!(Signal::Exact(-6250).describe(), "-62.5 dBm");
+    }
 }
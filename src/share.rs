@@ -0,0 +1,141 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use image::Luma;
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+const IWD_STORAGE_DIR: &str = "/var/lib/iwd";
+
+/// Builds the `WIFI:` payload phones understand when scanning a QR code.
+/// `security` is iwd's network type (`open`, `psk`, `8021x`).
+pub(crate) fn wifi_payload(ssid: &str, security: &str, passphrase: &str, hidden: bool) -> String {
+    let mut payload = String::from("WIFI:");
+    match security {
+        "open" => payload.push_str("T:nopass;"),
+        _ => payload.push_str("T:WPA;"),
+    }
+    payload.push_str(&format!("S:{};", escape(ssid)));
+    if security != "open" {
+        payload.push_str(&format!("P:{};", escape(passphrase)));
+    }
+    if hidden {
+        payload.push_str("H:true;");
+    }
+    payload.push(';');
+    payload
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Reads `Passphrase=` from the iwd profile of a PSK network. Profiles are
+/// normally readable by root only, so `None` is the common case.
+pub(crate) fn profile_passphrase(ssid: &str) -> Option<String> {
+    let path = Path::new(IWD_STORAGE_DIR).join(format!("{}.psk", profile_name(ssid)));
+    let contents = fs::read_to_string(path).ok()?;
+
+    let mut in_security = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_security = line == "[Security]";
+        } else if in_security && let Some(value) = line.strip_prefix("Passphrase=") {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// iwd stores profiles under the SSID itself when it only contains
+/// alphanumerics, ' ', '-' and '_', and under `=` plus the hex SSID otherwise.
+fn profile_name(ssid: &str) -> String {
+    let plain = ssid
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if plain {
+        ssid.to_string()
+    } else {
+        let hex: String = ssid.bytes().map(|b| format!("{b:02x}")).collect();
+        format!("={hex}")
+    }
+}
+
+pub(crate) fn export_png(payload: &str, path: &Path) -> io::Result<()> {
+    let image = encode(payload)?
+        .render::<Luma<u8>>()
+        .min_dimensions(512, 512)
+        .build();
+    image.save(path).map_err(io::Error::other)
+}
+
+pub(crate) fn export_svg(payload: &str, path: &Path) -> io::Result<()> {
+    let svg = encode(payload)?
+        .render::<svg::Color>()
+        .min_dimensions(512, 512)
+        .build();
+    fs::write(path, svg)
+}
+
+fn encode(payload: &str) -> io::Result<QrCode> {
+    QrCode::new(payload.as_bytes()).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape(r#"a\b;c,d:e"f"#), r#"a\\b\;c\,d\:e\"f"#);
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn psk_payload_escapes_ssid_and_passphrase() {
+        assert_eq!(
+            wifi_payload("My;Net,1", "psk", r#"pa:ss"\"#, false),
+            r#"WIFI:T:WPA;S:My\;Net\,1;P:pa\:ss\"\\;;"#
+        );
+    }
+
+    #[test]
+    fn open_payload_has_no_passphrase() {
+        assert_eq!(
+            wifi_payload("Cafe", "open", "ignored", false),
+            "WIFI:T:nopass;S:Cafe;;"
+        );
+    }
+
+    #[test]
+    fn hidden_payload() {
+        assert_eq!(
+            wifi_payload("Home", "psk", "secret", true),
+            "WIFI:T:WPA;S:Home;P:secret;H:true;;"
+        );
+        assert_eq!(
+            wifi_payload("Home", "open", "", true),
+            "WIFI:T:nopass;S:Home;H:true;;"
+        );
+    }
+
+    #[test]
+    fn plain_ssid_is_used_as_profile_name() {
+        assert_eq!(profile_name("Home Net_1-2"), "Home Net_1-2");
+    }
+
+    #[test]
+    fn other_ssids_use_hex_profile_name() {
+        assert_eq!(profile_name("a.b"), "=612e62");
+        assert_eq!(profile_name("Café!"), "=436166c3a921");
+        assert_eq!(profile_name("x;y"), "=783b79");
+    }
+}
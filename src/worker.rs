@@ -0,0 +1,518 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::dbus::{IwdDbus, IwdError};
+use crate::models::{
+    AccessPointInfo, AdHocInfo, AdapterInfo, DeviceInfo, DeviceMode, DppInfo, HiddenAccessPoint,
+    KnownNetwork, PkexInfo, StationDiagnostics, StationInfo, VisibleNetwork,
+};
+
+pub(crate) type OperationId = u64;
+
+const AP_PROFILE_DIR: &str = "/var/lib/iwd/ap";
+
+/// A D-Bus operation queued for the background worker.
+#[derive(Clone, Debug)]
+pub(crate) enum Command {
+    Refresh,
+    Scan {
+        device_path: String,
+    },
+    Connect {
+        network_path: String,
+        ssid: String,
+        username: Option<String>,
+        passphrase: Option<String>,
+    },
+    ConnectHidden {
+        device_path: String,
+        ssid: String,
+    },
+    Disconnect {
+        device_path: String,
+    },
+    Forget {
+        known_path: String,
+        name: String,
+    },
+    SetAutoConnect {
+        known_path: String,
+        enabled: bool,
+    },
+    SetAdapterPowered {
+        adapter_path: String,
+        powered: bool,
+    },
+    SetDevicePowered {
+        device_path: String,
+        powered: bool,
+    },
+    SetDeviceMode {
+        device_path: String,
+        mode: DeviceMode,
+    },
+    StartAccessPoint {
+        device_path: String,
+        ssid: String,
+        passphrase: String,
+    },
+    StartAccessPointProfile {
+        device_path: String,
+        profile: String,
+    },
+    StopAccessPoint {
+        device_path: String,
+    },
+    StartAdHoc {
+        device_path: String,
+        ssid: String,
+        passphrase: Option<String>,
+    },
+    StopAdHoc {
+        device_path: String,
+    },
+    WpsPushButton {
+        device_path: String,
+    },
+    WpsGeneratePin {
+        device_path: String,
+    },
+    WpsStartPin {
+        device_path: String,
+        pin: String,
+    },
+    WpsCancel {
+        device_path: String,
+    },
+    DppStartEnrollee {
+        device_path: String,
+    },
+    DppStartConfigurator {
+        device_path: String,
+    },
+    DppConfigureEnrollee {
+        device_path: String,
+        uri: String,
+    },
+    DppStop {
+        device_path: String,
+    },
+    PkexStartEnrollee {
+        device_path: String,
+        code: String,
+        identifier: Option<String>,
+    },
+    PkexConfigureEnrollee {
+        device_path: String,
+        code: String,
+        identifier: Option<String>,
+    },
+    PkexStartConfigurator {
+        device_path: String,
+    },
+    PkexStop {
+        device_path: String,
+    },
+    GetDiagnostics {
+        device_path: String,
+    },
+    RegisterAgent,
+    RegisterSignalLevelAgent {
+        device_path: String,
+    },
+}
+
+impl Command {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Command::Refresh => "Refreshing".to_string(),
+            Command::Scan { .. } => "Scanning".to_string(),
+            Command::Connect { ssid, .. } => format!("Connecting to `{ssid}`"),
+            Command::ConnectHidden { ssid, .. } => format!("Connecting to hidden `{ssid}`"),
+            Command::Disconnect { .. } => "Disconnecting".to_string(),
+            Command::Forget { name, .. } => format!("Forgetting `{name}`"),
+            Command::SetAutoConnect { .. } => "Updating AutoConnect".to_string(),
+            Command::SetAdapterPowered { powered, .. } => {
+                format!("Powering adapter {}", if *powered { "on" } else { "off" })
+            }
+            Command::SetDevicePowered { powered, .. } => {
+                format!("Powering device {}", if *powered { "on" } else { "off" })
+            }
+            Command::SetDeviceMode { mode, .. } => {
+                format!("Switching to {} mode", mode.label().to_lowercase())
+            }
+            Command::StartAccessPoint { ssid, .. } => format!("Starting hotspot `{ssid}`"),
+            Command::StartAccessPointProfile { profile, .. } => {
+                format!("Starting hotspot from profile `{profile}`")
+            }
+            Command::StopAccessPoint { .. } => "Stopping hotspot".to_string(),
+            Command::StartAdHoc { ssid, .. } => format!("Starting ad-hoc network `{ssid}`"),
+            Command::StopAdHoc { .. } => "Stopping ad-hoc network".to_string(),
+            Command::WpsPushButton { .. } => "Waiting for WPS push button".to_string(),
+            Command::WpsGeneratePin { .. } => "Generating WPS PIN".to_string(),
+            Command::WpsStartPin { .. } => "Connecting with WPS PIN".to_string(),
+            Command::WpsCancel { .. } => "Canceling WPS".to_string(),
+            Command::DppStartEnrollee { .. } => "Starting DPP enrollee".to_string(),
+            Command::DppStartConfigurator { .. } => "Starting DPP configurator".to_string(),
+            Command::DppConfigureEnrollee { .. } => "Configuring DPP enrollee".to_string(),
+            Command::DppStop { .. } => "Stopping DPP".to_string(),
+            Command::PkexStartEnrollee { .. } => "Starting shared code enrollee".to_string(),
+            Command::PkexConfigureEnrollee { .. } => "Configuring shared code enrollee".to_string(),
+            Command::PkexStartConfigurator { .. } => {
+                "Starting shared code configurator".to_string()
+            }
+            Command::PkexStop { .. } => "Stopping shared code pairing".to_string(),
+            Command::GetDiagnostics { .. } => "Reading diagnostics".to_string(),
+            Command::RegisterAgent => "Registering agent".to_string(),
+            Command::RegisterSignalLevelAgent { .. } => {
+                "Registering signal level agent".to_string()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Snapshot {
+    pub(crate) adapters: Vec<AdapterInfo>,
+    pub(crate) devices: Vec<DeviceInfo>,
+    pub(crate) stations: Vec<StationInfo>,
+    pub(crate) access_points: Vec<AccessPointInfo>,
+    pub(crate) ad_hocs: Vec<AdHocInfo>,
+    pub(crate) dpps: Vec<DppInfo>,
+    pub(crate) pkexes: Vec<PkexInfo>,
+    /// Names of the `.ap` profiles iwd can start, if the directory is readable.
+    pub(crate) ap_profiles: Vec<String>,
+    pub(crate) visible_networks: Vec<VisibleNetwork>,
+    pub(crate) hidden_access_points: Vec<HiddenAccessPoint>,
+    pub(crate) known_networks: Vec<KnownNetwork>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Outcome {
+    Refreshed(Box<Snapshot>),
+    GeneratedPin(String),
+    /// A `DPP:` URI to display for the peer to scan.
+    DppUri(String),
+    Diagnostics(Box<StationDiagnostics>),
+    Done,
+    /// The operation was canceled while still queued and never reached iwd.
+    Canceled,
+}
+
+#[derive(Debug)]
+pub(crate) struct Reply {
+    pub(crate) id: OperationId,
+    pub(crate) result: Result<Outcome, IwdError>,
+}
+
+/// Runs D-Bus operations one at a time on a dedicated thread so the UI never
+/// blocks on iwd.
+pub(crate) struct Worker {
+    backend: IwdDbus,
+    commands: Sender<(OperationId, Command)>,
+    reply_tx: Sender<Reply>,
+    replies: Receiver<Reply>,
+    canceled: Arc<Mutex<HashSet<OperationId>>>,
+    /// Id of the last queued operation the worker thread picked up.
+    started: Arc<AtomicU64>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+    next_id: OperationId,
+}
+
+impl std::fmt::Debug for Worker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Worker")
+            .field("next_id", &self.next_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Worker {
+    pub(crate) fn spawn<F>(backend: IwdDbus, notify: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let (command_tx, command_rx) = mpsc::channel();
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let canceled = Arc::new(Mutex::new(HashSet::new()));
+        let started = Arc::new(AtomicU64::new(0));
+        let notify: Arc<dyn Fn() + Send + Sync> = Arc::new(notify);
+
+        let thread_backend = backend.clone();
+        let thread_replies = reply_tx.clone();
+        let thread_canceled = Arc::clone(&canceled);
+        let thread_started = Arc::clone(&started);
+        let thread_notify = Arc::clone(&notify);
+        // If the thread cannot be started, `submit` keeps queueing into a
+        // closed channel and every operation stays pending; there is no way
+        // to talk to iwd without it anyway.
+        let _ = thread::Builder::new()
+            .name("iwd-worker".to_string())
+            .spawn(move || {
+                run(
+                    thread_backend,
+                    command_rx,
+                    thread_replies,
+                    thread_canceled,
+                    thread_started,
+                    thread_notify,
+                )
+            });
+
+        Self {
+            backend,
+            commands: command_tx,
+            reply_tx,
+            replies: reply_rx,
+            canceled,
+            started,
+            notify,
+            next_id: 0,
+        }
+    }
+
+    pub(crate) fn submit(&mut self, command: Command) -> OperationId {
+        self.next_id += 1;
+        let id = self.next_id;
+        let _ = self.commands.send((id, command));
+        id
+    }
+
+    /// Runs `command` on its own thread instead of queueing it, for calls that
+    /// block until iwd is done, such as connecting or WPS, and for calls that
+    /// must reach iwd while one of those is in flight.
+    pub(crate) fn submit_now(&mut self, command: Command) -> OperationId {
+        self.next_id += 1;
+        let id = self.next_id;
+        let backend = self.backend.clone();
+        let replies = self.reply_tx.clone();
+        let notify = Arc::clone(&self.notify);
+        let spawned = thread::Builder::new()
+            .name("iwd-worker-now".to_string())
+            .spawn(move || {
+                let result = execute(&backend, &command);
+                if replies.send(Reply { id, result }).is_ok() {
+                    notify();
+                }
+            });
+        if let Err(err) = spawned {
+            let _ = self.reply_tx.send(Reply {
+                id,
+                result: Err(IwdError::Internal(format!("Failed to start thread: {err}"))),
+            });
+        }
+        id
+    }
+
+    /// Whether an operation passed to `submit` is still waiting in the queue.
+    pub(crate) fn is_queued(&self, id: OperationId) -> bool {
+        id > self.started.load(Ordering::SeqCst)
+    }
+
+    /// Marks a queued operation as canceled so the worker skips it. An
+    /// operation that is already running cannot be interrupted from here.
+    pub(crate) fn cancel(&self, id: OperationId) {
+        if let Ok(mut canceled) = self.canceled.lock() {
+            canceled.insert(id);
+        }
+    }
+
+    pub(crate) fn try_replies(&self) -> Vec<Reply> {
+        self.replies.try_iter().collect()
+    }
+}
+
+fn run(
+    backend: IwdDbus,
+    commands: Receiver<(OperationId, Command)>,
+    replies: Sender<Reply>,
+    canceled: Arc<Mutex<HashSet<OperationId>>>,
+    started: Arc<AtomicU64>,
+    notify: Arc<dyn Fn() + Send + Sync>,
+) {
+    let take_canceled = |id| {
+        canceled
+            .lock()
+            .map(|mut canceled| canceled.remove(&id))
+            .unwrap_or(false)
+    };
+
+    for (id, command) in commands {
+        started.store(id, Ordering::SeqCst);
+        let result = if take_canceled(id) {
+            Ok(Outcome::Canceled)
+        } else {
+            let result = execute(&backend, &command);
+            take_canceled(id);
+            result
+        };
+
+        if replies.send(Reply { id, result }).is_err() {
+            return;
+        }
+        notify();
+    }
+}
+
+fn execute(backend: &IwdDbus, command: &Command) -> Result<Outcome, IwdError> {
+    match command {
+        Command::Refresh => {
+            let objects = backend.objects()?;
+            let adapters = objects.adapters()?;
+            let devices = objects.devices()?;
+            let stations = objects.stations();
+            let access_points = objects.access_points();
+            let ad_hocs = objects.ad_hocs();
+            let dpps = objects.dpps();
+            let pkexes = objects.pkexes();
+            let visible_networks = backend.list_visible_networks(&objects);
+            let known_networks = objects.known_networks()?;
+            let hidden_access_points = stations
+                .iter()
+                .filter_map(|s| backend.hidden_access_points(&s.path).ok())
+                .flatten()
+                .collect();
+            Ok(Outcome::Refreshed(Box::new(Snapshot {
+                adapters,
+                devices,
+                stations,
+                access_points,
+                ad_hocs,
+                dpps,
+                pkexes,
+                ap_profiles: list_ap_profiles(),
+                visible_networks,
+                hidden_access_points,
+                known_networks,
+            })))
+        }
+        Command::Scan { device_path } => backend.scan(device_path).map(|_| Outcome::Done),
+        Command::Connect { network_path, .. } => {
+            backend.connect_network(network_path).map(|_| Outcome::Done)
+        }
+        Command::ConnectHidden { device_path, ssid } => backend
+            .connect_hidden_network(device_path, ssid)
+            .map(|_| Outcome::Done),
+        Command::Disconnect { device_path } => {
+            backend.disconnect(device_path).map(|_| Outcome::Done)
+        }
+        Command::Forget { known_path, .. } => backend
+            .forget_known_network(known_path)
+            .map(|_| Outcome::Done),
+        Command::SetAutoConnect {
+            known_path,
+            enabled,
+        } => backend
+            .set_known_autoconnect(known_path, *enabled)
+            .map(|_| Outcome::Done),
+        Command::SetAdapterPowered {
+            adapter_path,
+            powered,
+        } => backend
+            .set_adapter_powered(adapter_path, *powered)
+            .map(|_| Outcome::Done),
+        Command::SetDevicePowered {
+            device_path,
+            powered,
+        } => backend
+            .set_device_powered(device_path, *powered)
+            .map(|_| Outcome::Done),
+        Command::SetDeviceMode { device_path, mode } => backend
+            .set_device_mode(device_path, mode)
+            .map(|_| Outcome::Done),
+        Command::StartAccessPoint {
+            device_path,
+            ssid,
+            passphrase,
+        } => backend
+            .start_access_point(device_path, ssid, passphrase)
+            .map(|_| Outcome::Done),
+        Command::StartAccessPointProfile {
+            device_path,
+            profile,
+        } => backend
+            .start_access_point_profile(device_path, profile)
+            .map(|_| Outcome::Done),
+        Command::StopAccessPoint { device_path } => backend
+            .stop_access_point(device_path)
+            .map(|_| Outcome::Done),
+        Command::StartAdHoc {
+            device_path,
+            ssid,
+            passphrase,
+        } => backend
+            .start_ad_hoc(device_path, ssid, passphrase.as_deref())
+            .map(|_| Outcome::Done),
+        Command::StopAdHoc { device_path } => {
+            backend.stop_ad_hoc(device_path).map(|_| Outcome::Done)
+        }
+        Command::WpsPushButton { device_path } => {
+            backend.wps_push_button(device_path).map(|_| Outcome::Done)
+        }
+        Command::WpsGeneratePin { device_path } => backend
+            .wps_generate_pin(device_path)
+            .map(Outcome::GeneratedPin),
+        Command::WpsStartPin { device_path, pin } => backend
+            .wps_start_pin(device_path, pin)
+            .map(|_| Outcome::Done),
+        Command::WpsCancel { device_path } => {
+            backend.cancel_wps(device_path).map(|_| Outcome::Done)
+        }
+        Command::DppStartEnrollee { device_path } => {
+            backend.dpp_start_enrollee(device_path).map(Outcome::DppUri)
+        }
+        Command::DppStartConfigurator { device_path } => backend
+            .dpp_start_configurator(device_path)
+            .map(Outcome::DppUri),
+        Command::DppConfigureEnrollee { device_path, uri } => backend
+            .dpp_configure_enrollee(device_path, uri)
+            .map(|_| Outcome::Done),
+        Command::DppStop { device_path } => backend.dpp_stop(device_path).map(|_| Outcome::Done),
+        Command::PkexStartEnrollee {
+            device_path,
+            code,
+            identifier,
+        } => backend
+            .pkex_start_enrollee(device_path, code, identifier.as_deref())
+            .map(|_| Outcome::Done),
+        Command::PkexConfigureEnrollee {
+            device_path,
+            code,
+            identifier,
+        } => backend
+            .pkex_configure_enrollee(device_path, code, identifier.as_deref())
+            .map(|_| Outcome::Done),
+        Command::PkexStartConfigurator { device_path } => backend
+            .pkex_start_configurator(device_path)
+            .map(|_| Outcome::Done),
+        Command::PkexStop { device_path } => backend.pkex_stop(device_path).map(|_| Outcome::Done),
+        Command::GetDiagnostics { device_path } => backend
+            .diagnostics(device_path)
+            .map(|diagnostics| Outcome::Diagnostics(Box::new(diagnostics))),
+        Command::RegisterAgent => backend.register_agent().map(|_| Outcome::Done),
+        Command::RegisterSignalLevelAgent { device_path } => backend
+            .register_signal_level_agent(device_path)
+            .map(|_| Outcome::Done),
+    }
+}
+
+/// Lists pre-provisioned access point profiles. The directory is usually only
+/// readable by root, in which case the profile name has to be typed in.
+fn list_ap_profiles() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(AP_PROFILE_DIR) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_suffix(".ap").map(str::to_string)
+        })
+        .collect();
+    profiles.sort();
+    profiles
+}